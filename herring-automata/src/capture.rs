@@ -0,0 +1,306 @@
+use crate::fold::{ascii_case_fold, full_case_edges, full_case_fold};
+use crate::subpattern::is_complement_group;
+use crate::{CaseFolding, Error, Nfa, Subpattern};
+use regex_syntax::hir::{Class, ClassUnicode, Hir, HirKind};
+use regex_syntax::utf8::Utf8Sequences;
+use std::collections::BTreeMap;
+
+/// Maximum number of instructions of a capture program, which bounds the unrolling of counted
+/// repetitions.
+const MAX_INSTS: usize = 10_000;
+
+/// Instruction of a capture program, which is executed by a Pike VM on the matched token.
+///
+/// The first target of a split is preferred, so the captures follow the usual leftmost-first
+/// semantics of greedy and non-greedy repetitions.
+#[derive(Clone, Debug)]
+pub enum CaptureInst {
+    /// Consume a byte in one of the ranges and continue at the given instruction.
+    Ranges(Vec<(u8, u8)>, usize),
+    Split(usize, usize),
+    Jump(usize),
+    /// Store the current offset in the given register.
+    Save(usize),
+    Match,
+}
+
+/// Tagged NFA, which records the spans of the named capture groups of a token regex.
+///
+/// The capture group with index `i` stores its start in register `2 * i` and its end in
+/// register `2 * i + 1`.
+#[derive(Clone, Debug)]
+pub struct Captures {
+    names: Vec<String>,
+    insts: Vec<CaptureInst>,
+    /// Literals and classes are folded during compilation for full and ASCII case folding.
+    case_folding: CaseFolding,
+    /// Whether the compilation stopped because the program exceeded `MAX_INSTS`.
+    too_large: bool,
+}
+
+impl Captures {
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+    pub fn insts(&self) -> &[CaptureInst] {
+        &self.insts
+    }
+
+    /// Returns `None` if the regex has no named capture groups.
+    pub fn from_regex_with_subpatterns(
+        regex: &str,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        case_folding: CaseFolding,
+        binary: bool,
+    ) -> Result<Option<Captures>, Error> {
+        let hir = Nfa::parse_regex(regex, subpatterns, case_folding, binary)?;
+        if !has_group(&hir, &|name| !is_complement_group(name)) {
+            return Ok(None);
+        }
+        // the words of a complement are not described by a program of the VM
+        if has_group(&hir, &is_complement_group) {
+            return Err(
+                "capture groups are not supported in regexes with complemented subpatterns".into(),
+            );
+        }
+        // the VM only runs on the token, so it cannot check assertions at its boundaries
+        if has_look(&hir) {
+            return Err("capture groups are not supported in regexes with anchors".into());
+        }
+        let mut captures = Captures {
+            names: vec![],
+            insts: vec![],
+            case_folding: Nfa::hir_case_folding(case_folding, binary),
+            too_large: false,
+        };
+        captures.compile(&hir);
+        if captures.too_large {
+            return Err(format!(
+                "capture groups are not supported in regexes with more than {MAX_INSTS} \
+                 instructions (e.g. because of large repetition counts)"
+            )
+            .into());
+        }
+        captures.insts.push(CaptureInst::Match);
+        Ok(Some(captures))
+    }
+
+    fn push(&mut self, inst: CaptureInst) -> usize {
+        self.insts.push(inst);
+        self.insts.len() - 1
+    }
+
+    /// Pushes an instruction, which is replaced once its targets are known.
+    fn push_placeholder(&mut self) -> usize {
+        self.push(CaptureInst::Jump(usize::MAX))
+    }
+
+    fn next(&self) -> usize {
+        self.insts.len()
+    }
+
+    fn patch_split(&mut self, split: usize, first: usize, second: usize) {
+        self.insts[split] = CaptureInst::Split(first, second);
+    }
+
+    fn compile_ranges(&mut self, ranges: Vec<(u8, u8)>) {
+        let next = self.next() + 1;
+        self.push(CaptureInst::Ranges(ranges, next));
+    }
+
+    fn compile_alternation(&mut self, alternatives: Vec<impl FnOnce(&mut Self)>) {
+        let mut jumps = vec![];
+        let count = alternatives.len();
+        for (i, alternative) in alternatives.into_iter().enumerate() {
+            let split = (i + 1 < count).then(|| self.push_placeholder());
+            let start = self.next();
+            alternative(self);
+            if let Some(split) = split {
+                jumps.push(self.push_placeholder());
+                let next = self.next();
+                self.patch_split(split, start, next);
+            }
+        }
+        let end = self.next();
+        for jump in jumps {
+            self.insts[jump] = CaptureInst::Jump(end);
+        }
+    }
+
+    fn compile_unicode_class(&mut self, class: &ClassUnicode) {
+        let mut sequences = vec![];
+        for r in class.ranges() {
+            sequences.extend(Utf8Sequences::new(r.start(), r.end()));
+        }
+        self.compile_alternation(
+            sequences
+                .into_iter()
+                .map(|sequence| {
+                    move |captures: &mut Self| {
+                        for range in sequence.as_slice() {
+                            captures.compile_ranges(vec![(range.start, range.end)]);
+                        }
+                    }
+                })
+                .collect(),
+        );
+    }
+
+    /// Matches all words with the same full case folding as the literal.
+    fn compile_full_case_literal(&mut self, literal: &str) {
+        let folded = full_case_fold(literal);
+        let mut positions = vec![];
+        let mut jumps = vec![];
+        for pos in 0..folded.len() {
+            positions.push(self.next());
+            let edges = full_case_edges(&folded, pos);
+            let count = edges.len();
+            for (i, (class, next)) in edges.into_iter().enumerate() {
+                let split = (i + 1 < count).then(|| self.push_placeholder());
+                self.compile_unicode_class(&class);
+                jumps.push((self.push_placeholder(), next));
+                if let Some(split) = split {
+                    let next_edge = self.next();
+                    self.patch_split(split, split + 1, next_edge);
+                }
+            }
+        }
+        positions.push(self.next());
+        for (jump, next) in jumps {
+            self.insts[jump] = CaptureInst::Jump(positions[next]);
+        }
+    }
+
+    fn compile(&mut self, hir: &Hir) {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => {}
+            HirKind::Literal(literal) => match std::str::from_utf8(&literal.0) {
+                Ok(literal) if self.case_folding == CaseFolding::Full => {
+                    self.compile_full_case_literal(literal)
+                }
+                _ => {
+                    for b in literal.0.iter() {
+                        if self.case_folding == CaseFolding::Ascii && b.is_ascii_alphabetic() {
+                            let (lower, upper) = (b.to_ascii_lowercase(), b.to_ascii_uppercase());
+                            self.compile_ranges(vec![(upper, upper), (lower, lower)]);
+                        } else {
+                            self.compile_ranges(vec![(*b, *b)]);
+                        }
+                    }
+                }
+            },
+            HirKind::Class(Class::Bytes(class)) => {
+                let mut class = class.clone();
+                if self.case_folding != CaseFolding::Disabled {
+                    class.case_fold_simple();
+                }
+                self.compile_ranges(
+                    class
+                        .ranges()
+                        .iter()
+                        .map(|r| (r.start(), r.end()))
+                        .collect(),
+                );
+            }
+            HirKind::Class(Class::Unicode(class)) => {
+                let mut class = class.clone();
+                match self.case_folding {
+                    CaseFolding::Full => class.case_fold_simple(),
+                    CaseFolding::Ascii => ascii_case_fold(&mut class),
+                    _ => {}
+                }
+                self.compile_unicode_class(&class);
+            }
+            HirKind::Repetition(repetition) => {
+                for _ in 0..repetition.min {
+                    if self.insts.len() > MAX_INSTS {
+                        self.too_large = true;
+                        return;
+                    }
+                    self.compile(&repetition.sub);
+                }
+                let optional_count = match repetition.max {
+                    Some(max) => max - repetition.min,
+                    None => 1,
+                };
+                let mut splits = vec![];
+                for _ in 0..optional_count {
+                    if self.insts.len() > MAX_INSTS {
+                        self.too_large = true;
+                        return;
+                    }
+                    splits.push(self.push_placeholder());
+                    self.compile(&repetition.sub);
+                }
+                if repetition.max.is_none() {
+                    self.push(CaptureInst::Jump(splits[0]));
+                }
+                let end = self.next();
+                for split in splits {
+                    if repetition.greedy {
+                        self.patch_split(split, split + 1, end);
+                    } else {
+                        self.patch_split(split, end, split + 1);
+                    }
+                }
+            }
+            HirKind::Capture(capture) => {
+                if let Some(name) = &capture.name {
+                    // the copies of a group in an unrolled repetition share its registers, so
+                    // the last iteration is captured
+                    let index = match self.names.iter().position(|n| **n == **name) {
+                        Some(index) => index,
+                        None => {
+                            self.names.push(name.to_string());
+                            self.names.len() - 1
+                        }
+                    };
+                    let register = 2 * index;
+                    self.push(CaptureInst::Save(register));
+                    self.compile(&capture.sub);
+                    self.push(CaptureInst::Save(register + 1));
+                } else {
+                    self.compile(&capture.sub);
+                }
+            }
+            HirKind::Concat(concat) => {
+                for hir in concat.iter() {
+                    self.compile(hir);
+                }
+            }
+            HirKind::Alternation(alternation) => {
+                self.compile_alternation(
+                    alternation
+                        .iter()
+                        .map(|hir| move |captures: &mut Self| captures.compile(hir))
+                        .collect(),
+                );
+            }
+        }
+    }
+}
+
+/// Whether the regex contains a named capture group, whose name matches the predicate.
+fn has_group(hir: &Hir, matches: &impl Fn(&str) -> bool) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => false,
+        HirKind::Repetition(repetition) => has_group(&repetition.sub, matches),
+        HirKind::Capture(capture) => {
+            capture.name.as_deref().is_some_and(matches) || has_group(&capture.sub, matches)
+        }
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+            hirs.iter().any(|hir| has_group(hir, matches))
+        }
+    }
+}
+
+/// Whether the regex contains an anchor or a word boundary.
+fn has_look(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Look(_) => true,
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) => false,
+        HirKind::Repetition(repetition) => has_look(&repetition.sub),
+        HirKind::Capture(capture) => has_look(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(has_look),
+    }
+}
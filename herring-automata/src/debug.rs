@@ -1,4 +1,4 @@
-use crate::{Automaton, Output, Pattern};
+use crate::{Automaton, ByteKind, Output, Pattern};
 
 impl core::fmt::Debug for Pattern {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
@@ -42,17 +42,25 @@ impl core::fmt::Debug for Output {
 }
 
 impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
+    fn is_accepting(&self, state: usize) -> bool {
+        self.accepts.keys().any(|node| state == node.0)
+            || self.next_accepts.keys().any(|node| state == node.0)
+    }
+
     pub fn print_graphviz(&self, name: &str) -> std::io::Result<()> {
         use std::io::Write;
         let mut f = std::fs::File::create(name)?;
 
         writeln!(f, "digraph {{\nrankdir=LR;")?;
         writeln!(f, "start [shape=none];\nstart -> {};", self.start.0)?;
+        for (kind, start) in ByteKind::ALL.iter().zip(self.starts.iter()).skip(1) {
+            writeln!(f, "start -> {} [label=\"{kind:?}\"];", start.0)?;
+        }
         for (i, node) in self.states.iter().enumerate() {
             writeln!(
                 f,
                 "{i} [shape={}];",
-                if self.accepts.iter().any(|(node, _)| i == node.0) {
+                if self.is_accepting(i) {
                     "doublecircle"
                 } else {
                     "circle"
@@ -64,6 +72,21 @@ impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
                     .replace("\"", "\\\"");
                 writeln!(f, "{i} -> {} [label=\"{label}\"];", t.to.0)?;
             }
+            for (look, to) in node.looks.iter() {
+                writeln!(f, "{i} -> {} [label=\"{look:?}\"];", to.0)?;
+            }
+        }
+        for (node, outputs) in self.next_accepts.iter() {
+            for (kind, output) in ByteKind::ALL.iter().zip(outputs.iter()) {
+                if let Some(output) = output {
+                    writeln!(f, "\"{output:?}\" [shape=box];")?;
+                    writeln!(
+                        f,
+                        "{} -> \"{output:?}\" [style=\"dashed\", label=\"{kind:?}\"];",
+                        node.0
+                    )?;
+                }
+            }
         }
         for (node, output) in self.accepts.iter() {
             if let Some(output) = output {
@@ -84,11 +107,14 @@ impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
             "style start fill:#FFFFFF00, stroke:#FFFFFF00\nstart-->{};",
             self.start.0
         )?;
+        for (kind, start) in ByteKind::ALL.iter().zip(self.starts.iter()).skip(1) {
+            writeln!(f, "start -- \"{kind:?}\" --> {}", start.0)?;
+        }
         for (i, node) in self.states.iter().enumerate() {
             writeln!(
                 f,
                 "{i}@{{shape: {}}}",
-                if self.accepts.iter().any(|(node, _)| i == node.0) {
+                if self.is_accepting(i) {
                     "dbl-circ"
                 } else {
                     "circ"
@@ -98,6 +124,28 @@ impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
                 let label = format!("{:?}", t.when).replace("\"", "#34;");
                 writeln!(f, "{i} -- \"{label}\" --> {}", t.to.0)?;
             }
+            for (look, to) in node.looks.iter() {
+                writeln!(f, "{i} -- \"{look:?}\" --> {}", to.0)?;
+            }
+        }
+        for (node, outputs) in self.next_accepts.iter() {
+            for (kind, output) in ByteKind::ALL.iter().zip(outputs.iter()) {
+                if let Some(output) = output {
+                    writeln!(
+                        f,
+                        "{}_{}[{output:?}]@{{shape: rect}}",
+                        output.value.0.replace(' ', "_"),
+                        output.value.1
+                    )?;
+                    writeln!(
+                        f,
+                        "{} -. \"{kind:?}\" .-> {}_{}",
+                        node.0,
+                        output.value.0.replace(' ', "_"),
+                        output.value.1
+                    )?;
+                }
+            }
         }
         for (node, output) in self.accepts.iter() {
             if let Some(output) = output {
@@ -1,97 +1,500 @@
-use crate::{Dfa, Output, StateRef};
-use std::collections::{BTreeSet, HashMap};
+use crate::{ByteClasses, ByteKind, Dfa, Error, Nfa, Output, Pattern, StateRef, TokenEnd};
+use regex_syntax::hir::ClassBytes;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// Decoder state of UTF-8 encoded words, given by the number of remaining continuation bytes of
+/// the current character and the range of the next one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Utf8State {
+    remaining: u8,
+    next: (u8, u8),
+}
+
+impl Utf8State {
+    const BOUNDARY: Utf8State = Utf8State {
+        remaining: 0,
+        next: (0, 0),
+    };
+
+    /// Decodes the next byte, which is always valid for binary words.
+    fn step(self, b: u8, utf8: bool) -> Option<Self> {
+        if !utf8 {
+            return Some(self);
+        }
+        if self.remaining > 0 {
+            return (self.next.0..=self.next.1)
+                .contains(&b)
+                .then_some(Utf8State {
+                    remaining: self.remaining - 1,
+                    next: (0x80, 0xBF),
+                });
+        }
+        let (remaining, next) = match b {
+            0x00..=0x7F => return Some(Self::BOUNDARY),
+            0xC2..=0xDF => (1, (0x80, 0xBF)),
+            0xE0 => (2, (0xA0, 0xBF)),
+            0xED => (2, (0x80, 0x9F)),
+            0xE1..=0xEF => (2, (0x80, 0xBF)),
+            0xF0 => (3, (0x90, 0xBF)),
+            0xF4 => (3, (0x80, 0x8F)),
+            0xF1..=0xF3 => (3, (0x80, 0xBF)),
+            _ => return None,
+        };
+        Some(Utf8State { remaining, next })
+    }
+
+    /// Completes the current character with the smallest continuation bytes.
+    fn complete(mut self, word: &mut Vec<u8>) {
+        while self.remaining > 0 {
+            word.push(self.next.0);
+            self = self.step(self.next.0, true).unwrap();
+        }
+    }
+}
+
+/// Bytes in the order they are tried by searches for words, so printable words are preferred.
+fn search_bytes() -> impl Iterator<Item = u8> {
+    (b' '..=b'~').chain(0..b' ').chain(0x7F..=0xFF)
+}
+
+/// Partition of the states, where the states of each block are stored contiguously.
+struct Partition {
+    elements: Vec<usize>,
+    location: Vec<usize>,
+    block_of: Vec<usize>,
+    blocks: Vec<(usize, usize)>,
+    marked: Vec<usize>,
+}
+
+impl Partition {
+    fn new(initial_blocks: Vec<Vec<usize>>, state_count: usize) -> Self {
+        let mut partition = Self {
+            elements: Vec::with_capacity(state_count),
+            location: vec![0; state_count],
+            block_of: vec![0; state_count],
+            blocks: Vec::with_capacity(initial_blocks.len()),
+            marked: vec![0; initial_blocks.len()],
+        };
+        for states in initial_blocks {
+            let start = partition.elements.len();
+            for s in states {
+                partition.location[s] = partition.elements.len();
+                partition.block_of[s] = partition.blocks.len();
+                partition.elements.push(s);
+            }
+            partition.blocks.push((start, partition.elements.len()));
+        }
+        partition
+    }
+    fn block(&self, block: usize) -> &[usize] {
+        let (start, end) = self.blocks[block];
+        &self.elements[start..end]
+    }
+    /// Moves the state to the marked front part of its block.
+    fn mark(&mut self, s: usize) {
+        let block = self.block_of[s];
+        let (start, _) = self.blocks[block];
+        let marked_end = start + self.marked[block];
+        let loc = self.location[s];
+        if loc < marked_end {
+            return;
+        }
+        let other = self.elements[marked_end];
+        self.elements.swap(loc, marked_end);
+        self.location[other] = loc;
+        self.location[s] = marked_end;
+        self.marked[block] += 1;
+    }
+    /// Splits the marked part off of the block, if the block is not entirely marked.
+    ///
+    /// Returns the index of the new block.
+    fn split(&mut self, block: usize) -> Option<usize> {
+        let (start, end) = self.blocks[block];
+        let marked_end = start + std::mem::take(&mut self.marked[block]);
+        if marked_end == start || marked_end == end {
+            return None;
+        }
+        let new_block = self.blocks.len();
+        self.blocks[block] = (marked_end, end);
+        self.blocks.push((start, marked_end));
+        self.marked.push(0);
+        for &s in &self.elements[start..marked_end] {
+            self.block_of[s] = new_block;
+        }
+        Some(new_block)
+    }
+}
 
 impl Dfa {
+    /// Replaces the values of all outputs.
+    pub fn map_values(mut self, f: impl Fn(&(String, usize)) -> (String, usize)) -> Self {
+        let outputs = self.accepts.values_mut();
+        for output in outputs
+            .chain(self.next_accepts.values_mut().flatten())
+            .flatten()
+        {
+            output.value = f(&output.value);
+        }
+        self
+    }
+
     /// Hopcroft's algorithm
     pub fn into_minimized(self) -> Self {
-        let mut accept_partitions = HashMap::<Output, Vec<StateRef>>::new();
-        for (state, output) in self.accepts.iter() {
-            if let Some(output) = output {
-                accept_partitions
-                    .entry(output.clone())
-                    .and_modify(|e| e.push(*state))
-                    .or_insert(vec![*state]);
-            }
-        }
-        let mut p = BTreeSet::new();
-        for (_, states) in accept_partitions {
-            p.insert(BTreeSet::from_iter(states));
-        }
-        let mut non_accept = BTreeSet::new();
-        for s in 0..self.states.len() {
-            if !self.accepts.contains_key(&StateRef(s)) {
-                non_accept.insert(StateRef(s));
-            }
-        }
-        if !non_accept.is_empty() {
-            p.insert(non_accept);
-        }
-        let mut w = p.clone();
-
-        while !w.is_empty() {
-            let a = w.pop_last().unwrap();
-            for b in u8::MIN..=u8::MAX {
-                let mut x = BTreeSet::new();
-                for (num, s) in self.states.iter().enumerate() {
-                    for t in s.transitions.iter() {
-                        let from = StateRef(num);
-                        if a.contains(&t.to) && t.when.contains(b) {
-                            x.insert(from);
-                        }
-                    }
+        let byte_classes = self.byte_classes();
+        let class_count = byte_classes.iter().count();
+        let state_count = self.states.len();
+
+        // predecessors of each state for each class
+        let mut inverse = vec![vec![]; state_count * class_count];
+        for (num, s) in self.states.iter().enumerate() {
+            for (class, (b, _)) in byte_classes.iter().enumerate() {
+                if let Some(t) = s.transitions.iter().find(|t| t.when.contains(b)) {
+                    inverse[t.to.0 * class_count + class].push(num);
                 }
-                if x.is_empty() {
-                    continue;
+            }
+        }
+
+        type Key<'a> = (Option<&'a Option<Output>>, Option<&'a Vec<Option<Output>>>);
+        let mut initial_blocks = HashMap::<Key, Vec<usize>>::new();
+        for s in 0..state_count {
+            initial_blocks
+                .entry((
+                    self.accepts.get(&StateRef(s)),
+                    self.next_accepts.get(&StateRef(s)),
+                ))
+                .or_default()
+                .push(s);
+        }
+        let mut p = Partition::new(initial_blocks.into_values().collect(), state_count);
+
+        // the transitions are partial, so all initial blocks are required as splitters, as the
+        // implicit dead state is the only block that can be omitted
+        let mut w = vec![];
+        let mut in_w = vec![];
+        for block in 0..p.blocks.len() {
+            for class in 0..class_count {
+                w.push((block, class));
+                in_w.push(true);
+            }
+        }
+
+        let mut touched = vec![];
+        while let Some((a, class)) = w.pop() {
+            in_w[a * class_count + class] = false;
+            for &s in p.block(a) {
+                for &pred in &inverse[s * class_count + class] {
+                    touched.push(pred);
                 }
-                let mut replacements = Vec::with_capacity(p.len());
-                for y in p.iter() {
-                    let cut = x.intersection(y).copied().collect::<BTreeSet<_>>();
-                    let diff = y.difference(&x).copied().collect::<BTreeSet<_>>();
-                    if !cut.is_empty() && !diff.is_empty() {
-                        if w.contains(y) {
-                            w.remove(y);
-                            w.insert(cut.clone());
-                            w.insert(diff.clone());
-                        } else if cut.len() < diff.len() {
-                            w.insert(cut.clone());
-                        } else {
-                            w.insert(diff.clone());
-                        }
-                        replacements.push((y.clone(), (cut, diff)));
+            }
+            for &s in touched.iter() {
+                p.mark(s);
+            }
+            for s in touched.drain(..) {
+                let y = p.block_of[s];
+                let Some(new_block) = p.split(y) else {
+                    continue;
+                };
+                in_w.resize(p.blocks.len() * class_count, false);
+                let (y_len, new_len) = (p.block(y).len(), p.block(new_block).len());
+                for c in 0..class_count {
+                    let smaller = if in_w[y * class_count + c] || new_len <= y_len {
+                        new_block
+                    } else {
+                        y
+                    };
+                    if !in_w[smaller * class_count + c] {
+                        in_w[smaller * class_count + c] = true;
+                        w.push((smaller, c));
                     }
                 }
-                for (y, (cut, diff)) in replacements.into_iter() {
-                    p.remove(&y);
-                    p.insert(cut);
-                    p.insert(diff);
-                }
             }
         }
 
+        // blocks are numbered by their smallest state, which keeps the start state at zero
+        let mut blocks = (0..p.blocks.len())
+            .map(|block| {
+                let mut states = p.block(block).to_vec();
+                states.sort();
+                states
+            })
+            .collect::<Vec<_>>();
+        blocks.sort();
+
         let mut automaton = Dfa::new();
-        let mut new_states = HashMap::new();
-        for partition in p.iter() {
-            let state = if partition.contains(&self.start) {
+        let mut new_states = vec![StateRef(0); state_count];
+        for states in blocks.iter() {
+            let state = if states.contains(&self.start.0) {
                 automaton.start
             } else {
                 automaton.add()
             };
-            for s in partition {
-                new_states.insert(s, state);
+            for &s in states {
+                new_states[s] = state;
             }
         }
-        for partition in p.iter() {
-            for s in partition {
-                let state = new_states[&s];
-                if let Some(tok) = self.accepts.get(s) {
+        for states in blocks.iter() {
+            for &s in states {
+                let state = new_states[s];
+                if let Some(tok) = self.accepts.get(&StateRef(s)) {
                     let _ = automaton.set_accept_output(state, tok.clone());
                 }
-                for t in self.states[s.0].transitions.iter() {
-                    let to = new_states[&t.to];
+                if let Some(outputs) = self.next_accepts.get(&StateRef(s)) {
+                    automaton.next_accepts.insert(state, outputs.clone());
+                }
+                for t in self.states[s].transitions.iter() {
+                    let to = new_states[t.to.0];
                     automaton.add_transition(state, t.when.clone(), to);
                 }
             }
         }
+        automaton.starts = self.starts.iter().map(|s| new_states[s.0]).collect();
         automaton
     }
+
+    /// Converts the DFA back to an NFA, which fails if the DFA depends on the bytes around the
+    /// token, as the NFA cannot represent this.
+    pub fn into_nfa(self) -> Result<Nfa, Error> {
+        if !self.starts.is_empty() || !self.next_accepts.is_empty() {
+            return Err("look-around assertions are not supported here".into());
+        }
+        Ok(Nfa {
+            start: self.start,
+            accepts: self.accepts,
+            states: self.states,
+            starts: vec![],
+            next_accepts: BTreeMap::new(),
+        })
+    }
+
+    fn next_state(&self, state: StateRef, b: u8) -> Option<StateRef> {
+        self.states[state.0]
+            .transitions
+            .iter()
+            .find(|t| t.when.contains(b))
+            .map(|t| t.to)
+    }
+
+    /// Product construction, which accepts the words accepted by both automata.
+    ///
+    /// The outputs are taken from this automaton.
+    pub fn intersection(&self, other: &Dfa) -> Dfa {
+        let mut automaton = Dfa::new();
+        let byte_classes = ByteClasses::new(
+            self.states
+                .iter()
+                .chain(other.states.iter())
+                .flat_map(|state| state.transitions.iter().map(|t| &t.when)),
+        );
+        let mut dstates = HashMap::new();
+        dstates.insert((self.start, other.start), automaton.start);
+        let mut todo = vec![(self.start, other.start)];
+        while let Some((a, b)) = todo.pop() {
+            let state = dstates[&(a, b)];
+            if let (Some(output), Some(_)) = (self.accepts.get(&a), other.accepts.get(&b)) {
+                automaton.accepts.insert(state, output.clone());
+            }
+            for (byte, class) in byte_classes.iter() {
+                let (Some(next_a), Some(next_b)) =
+                    (self.next_state(a, byte), other.next_state(b, byte))
+                else {
+                    continue;
+                };
+                let next_state = *dstates.entry((next_a, next_b)).or_insert_with(|| {
+                    todo.push((next_a, next_b));
+                    automaton.add()
+                });
+                automaton.add_transition(state, class.clone(), next_state);
+            }
+        }
+        automaton
+    }
+
+    /// Accepts exactly the byte strings, which are not accepted by this automaton.
+    pub fn complement(&self) -> Dfa {
+        let mut automaton = Dfa::new();
+        automaton.start = self.start;
+        automaton.states = self.states.clone();
+        // the transitions are partial, so the missing ones lead to an accepting dead state
+        let dead = automaton.add();
+        let mut all = ClassBytes::empty();
+        all.negate();
+        automaton.add_transition(dead, Pattern::from_class(all), dead);
+        automaton.set_accept(dead);
+        for (num, state) in self.states.iter().enumerate() {
+            let mut missing = ClassBytes::empty();
+            for t in state.transitions.iter() {
+                missing.union(&t.when.0);
+            }
+            missing.negate();
+            if !missing.ranges().is_empty() {
+                automaton.add_transition(StateRef(num), Pattern::from_class(missing), dead);
+            }
+            if !self.accepts.contains_key(&StateRef(num)) {
+                automaton.set_accept(StateRef(num));
+            }
+        }
+        automaton
+    }
+
+    /// Accepts the words accepted by this automaton, but not by the other one.
+    pub fn difference(&self, other: &Dfa) -> Dfa {
+        self.intersection(&other.complement())
+    }
+
+    fn start_after(&self, prev: ByteKind) -> StateRef {
+        match ByteKind::ALL.iter().position(|kind| *kind == prev) {
+            Some(index) if !self.starts.is_empty() => self.starts[index],
+            _ => self.start,
+        }
+    }
+
+    /// Returns the output of the token accepted in the state, if the input continues with the
+    /// given byte.
+    fn accept_before(&self, state: StateRef, next: Option<u8>) -> Option<&Output> {
+        if let Some(outputs) = self.next_accepts.get(&state) {
+            let kind = ByteKind::of(next);
+            let index = ByteKind::ALL.iter().position(|k| *k == kind)?;
+            outputs[index].as_ref()
+        } else {
+            self.accepts.get(&state)?.as_ref()
+        }
+    }
+
+    /// Splits the input into the longest tokens like the generated lexer and returns the offset of
+    /// the first byte, where no token starts.
+    ///
+    /// Callbacks are assumed to accept their tokens.
+    pub fn first_error(&self, input: &[u8]) -> Option<usize> {
+        let mut start = 0;
+        while start < input.len() {
+            let prev = ByteKind::of(start.checked_sub(1).map(|i| input[i]));
+            let mut state = self.start_after(prev);
+            let mut offset = start;
+            let mut end = None;
+            loop {
+                if let Some(output) = self.accept_before(state, input.get(offset).copied()) {
+                    end = Some(match output.end() {
+                        TokenEnd::MatchEnd => offset,
+                        TokenEnd::BeforeMatchEnd(len) => offset - len,
+                        TokenEnd::AfterMatchStart(len) => start + len,
+                    });
+                }
+                match input.get(offset).and_then(|b| self.next_state(state, *b)) {
+                    Some(next) => {
+                        state = next;
+                        offset += 1;
+                    }
+                    None => break,
+                }
+            }
+            match end {
+                Some(end) if end > start => start = end,
+                _ => return Some(start),
+            }
+        }
+        None
+    }
+
+    /// Searches for a shortest input, which cannot be split into tokens.
+    ///
+    /// At the start of a token the lexer fails, if no prefix of the remaining input is accepted,
+    /// so the search follows the transitions from each start state through states, which do not
+    /// accept before the next byte, until a dead transition or the end of the input is reached.
+    /// If the start state depends on the previous byte, the remaining input is preceded by a
+    /// shortest token ending with a byte of that kind.
+    /// Only valid UTF-8 is searched, unless the lexer is binary.
+    /// Callbacks are assumed to accept their tokens.
+    pub fn shortest_unlexable(&self, utf8: bool) -> Option<Vec<u8>> {
+        let mut shortest: Option<Vec<u8>> = None;
+        let kinds: &[ByteKind] = if self.starts.is_empty() {
+            &[ByteKind::Boundary]
+        } else {
+            &ByteKind::ALL
+        };
+        for kind in kinds.iter().copied() {
+            let Some(rest) = self.shortest_rejected(self.start_after(kind), utf8) else {
+                continue;
+            };
+            let word = if kind == ByteKind::Boundary {
+                Some(rest)
+            } else {
+                self.shortest_prefixed(kind, &rest, utf8)
+            };
+            if let Some(word) = word {
+                if shortest.as_ref().is_none_or(|w| word.len() < w.len()) {
+                    shortest = Some(word);
+                }
+            }
+        }
+        shortest
+    }
+
+    /// Searches for a shortest word without an accepted prefix, when starting from the state.
+    fn shortest_rejected(&self, start: StateRef, utf8: bool) -> Option<Vec<u8>> {
+        let mut visited = HashSet::from([(start, Utf8State::BOUNDARY)]);
+        // a dead transition is queued without a state, so words are returned in the order of
+        // their length
+        let mut queue = VecDeque::from([(Some(start), Utf8State::BOUNDARY, vec![])]);
+        while let Some((state, decoder, mut word)) = queue.pop_front() {
+            let Some(state) = state else {
+                // the lexer fails regardless of the bytes completing the character
+                decoder.complete(&mut word);
+                return Some(word);
+            };
+            if !word.is_empty()
+                && decoder == Utf8State::BOUNDARY
+                && self.accept_before(state, None).is_none()
+            {
+                return Some(word);
+            }
+            for b in search_bytes() {
+                if !word.is_empty() && self.accept_before(state, Some(b)).is_some() {
+                    continue;
+                }
+                let Some(next_decoder) = decoder.step(b, utf8) else {
+                    continue;
+                };
+                let next = self.next_state(state, b);
+                if next.is_none_or(|next| visited.insert((next, next_decoder))) {
+                    let mut next_word = word.clone();
+                    next_word.push(b);
+                    queue.push_back((next, next_decoder, next_word));
+                }
+            }
+        }
+        None
+    }
+
+    /// Searches for a shortest token ending with a byte of the kind, after which the rest cannot
+    /// be lexed.
+    fn shortest_prefixed(&self, kind: ByteKind, rest: &[u8], utf8: bool) -> Option<Vec<u8>> {
+        let start = self.start_after(ByteKind::Boundary);
+        let mut visited = HashSet::from([(start, ByteKind::Boundary, Utf8State::BOUNDARY)]);
+        let mut queue = VecDeque::from([(start, Utf8State::BOUNDARY, vec![])]);
+        while let Some((state, decoder, word)) = queue.pop_front() {
+            let last = ByteKind::of(word.last().copied());
+            if last == kind
+                && decoder == Utf8State::BOUNDARY
+                && self.accept_before(state, rest.first().copied()).is_some()
+            {
+                let candidate = [word.as_slice(), rest].concat();
+                if self.first_error(&candidate).is_some() {
+                    return Some(candidate);
+                }
+            }
+            for b in search_bytes() {
+                let (Some(next), Some(next_decoder)) =
+                    (self.next_state(state, b), decoder.step(b, utf8))
+                else {
+                    continue;
+                };
+                if visited.insert((next, ByteKind::of(Some(b)), next_decoder)) {
+                    let mut next_word = word.clone();
+                    next_word.push(b);
+                    queue.push_back((next, next_decoder, next_word));
+                }
+            }
+        }
+        None
+    }
 }
@@ -1,17 +1,46 @@
 #![forbid(unsafe_code)]
 
+mod capture;
 mod debug;
 mod dfa;
+mod fold;
+mod look;
 mod nfa;
+mod serialize;
+mod subpattern;
 
-use regex_syntax::hir::{ClassBytes, ClassBytesRange};
+pub use capture::{CaptureInst, Captures};
+pub use look::ByteKind;
+use regex_syntax::hir::{ClassBytes, ClassBytesRange, Look};
 use regex_syntax::utf8::Utf8Range;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug)]
 pub struct Error {
     pub message: String,
+    /// Outputs of tokens with the same priority, which may match the same word.
+    pub conflict: Option<Box<(Output, Output)>>,
+    /// Location of the error in the regex or in a subpattern used by it.
+    pub location: Option<Location>,
+}
+
+/// Regex, which is inserted for the references `(?&name)` in other regexes.
+#[derive(Clone, Debug)]
+pub struct Subpattern {
+    pub regex: String,
+    /// The subpattern matches the words, which are not matched by the regex.
+    pub complement: bool,
+}
+
+/// Byte range in a regex or in one of its subpatterns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Name of the subpattern, or `None` for the regex itself.
+    pub subpattern: Option<String>,
+    pub range: std::ops::Range<usize>,
 }
 
 #[derive(Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -20,42 +49,86 @@ pub struct StateRef(usize);
 #[derive(Clone, Default, Debug)]
 pub struct State {
     transitions: Vec<Transition>,
+    looks: Vec<(Look, StateRef)>,
 }
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Pattern(ClassBytes);
 
+/// Partition of the bytes into classes, which are not distinguished by any pattern of an automaton.
+pub(crate) struct ByteClasses {
+    classes: Vec<Pattern>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Transition {
     when: Pattern,
     to: StateRef,
 }
 
+/// End of a token relative to the matched word, which differs for tokens with trailing context.
+#[derive(Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+pub enum TokenEnd {
+    /// The token ends at the end of the match.
+    #[default]
+    MatchEnd,
+    /// The token ends the given number of bytes before the end of the match.
+    BeforeMatchEnd(usize),
+    /// The token ends the given number of bytes after the start of the match.
+    AfterMatchStart(usize),
+}
+
+/// Case folding applied to a token, so it matches all words with the same folding.
+#[derive(Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+pub enum CaseFolding {
+    /// The token is case-sensitive.
+    #[default]
+    Disabled,
+    /// Each character is folded to a single character.
+    Simple,
+    /// Only ASCII letters are folded, so other characters keep their case.
+    Ascii,
+    /// Characters may also be folded to a sequence of characters (e.g. `ß` to `ss`).
+    Full,
+}
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct Output {
     priority: usize,
     value: (String, usize),
+    end: TokenEnd,
+    /// Name of the token in diagnostics, which defaults to the name of the value.
+    name: Option<String>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Automaton<const IS_DETERMINISTIC: bool> {
     start: StateRef,
-    accepts: HashMap<StateRef, Option<Output>>,
+    accepts: BTreeMap<StateRef, Option<Output>>,
     states: Vec<State>,
+    /// Start states for each `ByteKind` of the byte before the token, if it is relevant.
+    starts: Vec<StateRef>,
+    /// Outputs for each `ByteKind` of the byte after the token, if acceptance depends on it.
+    next_accepts: BTreeMap<StateRef, Vec<Option<Output>>>,
 }
 pub type Nfa = Automaton<false>;
 pub type Dfa = Automaton<true>;
 
+#[derive(Clone)]
 pub struct Token {
     nfa: Nfa,
     priority: usize,
     value: (String, usize),
+    end: TokenEnd,
+    name: Option<String>,
 }
 
 impl<T: std::fmt::Display> From<T> for Error {
     fn from(value: T) -> Self {
         Self {
             message: value.to_string(),
+            conflict: None,
+            location: None,
         }
     }
 }
@@ -101,6 +174,47 @@ impl Pattern {
         self.0.ranges()
     }
 }
+
+impl ByteClasses {
+    fn new<'a>(patterns: impl Iterator<Item = &'a Pattern>) -> Self {
+        let mut class_of = [0usize; 256];
+        let mut class_count = 1;
+        for pattern in patterns {
+            if pattern.is_empty() {
+                continue;
+            }
+            let mut split = HashMap::new();
+            for b in u8::MIN..=u8::MAX {
+                if pattern.contains(b) {
+                    class_of[b as usize] =
+                        *split.entry(class_of[b as usize]).or_insert_with(|| {
+                            class_count += 1;
+                            class_count - 1
+                        });
+                }
+            }
+        }
+        // classes are ordered by their smallest byte, so iterating over the classes visits
+        // transitions in the same order as iterating over the bytes
+        let mut index = HashMap::new();
+        let mut classes = Vec::<Pattern>::new();
+        for b in u8::MIN..=u8::MAX {
+            let i = *index.entry(class_of[b as usize]).or_insert_with(|| {
+                classes.push(Pattern::empty());
+                classes.len() - 1
+            });
+            classes[i].union(&Pattern::from_byte(b));
+        }
+        Self { classes }
+    }
+    /// Returns the classes together with their smallest byte, which represents the class.
+    fn iter(&self) -> impl Iterator<Item = (u8, &Pattern)> {
+        self.classes
+            .iter()
+            .map(|class| (class.ranges()[0].start(), class))
+    }
+}
+
 impl PartialOrd for Pattern {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -113,12 +227,55 @@ impl Ord for Pattern {
 }
 
 impl Output {
-    fn new(priority: usize, value: (String, usize)) -> Self {
-        Self { priority, value }
+    fn new(priority: usize, value: (String, usize), end: TokenEnd, name: Option<String>) -> Self {
+        Self {
+            priority,
+            value,
+            end,
+            name,
+        }
     }
     pub fn value(&self) -> &(String, usize) {
         &self.value
     }
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.value.0)
+    }
+    pub fn priority(&self) -> usize {
+        self.priority
+    }
+    pub fn end(&self) -> TokenEnd {
+        self.end
+    }
+    /// Selects the output with the higher priority.
+    ///
+    /// Returns `None` if the current output should be kept.
+    fn select(
+        current_output: Option<Output>,
+        output: Option<Output>,
+    ) -> Result<Option<Option<Output>>, Error> {
+        if let (Some(current_output), Some(output)) = (&current_output, &output) {
+            match current_output.priority.cmp(&output.priority) {
+                Ordering::Less => {}
+                // several accept states of a token, e.g. of a complement, carry the same output
+                Ordering::Equal if current_output.value == output.value => return Ok(None),
+                Ordering::Equal => {
+                    return Err(Error {
+                        message: format!(
+                            "tokens `{}` and `{}` both have priority {} and may match the same word",
+                            current_output.name(),
+                            output.name(),
+                            current_output.priority,
+                        ),
+                        conflict: Some(Box::new((current_output.clone(), output.clone()))),
+                        location: None,
+                    });
+                }
+                Ordering::Greater => return Ok(None),
+            }
+        }
+        Ok(Some(output))
+    }
 }
 
 impl State {
@@ -146,17 +303,32 @@ impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
     pub fn start(&self) -> StateRef {
         self.start
     }
-    pub fn accepts(&self) -> &HashMap<StateRef, Option<Output>> {
+    pub fn accepts(&self) -> &BTreeMap<StateRef, Option<Output>> {
         &self.accepts
     }
     pub fn states(&self) -> &[State] {
         &self.states
     }
+    pub fn starts(&self) -> &[StateRef] {
+        &self.starts
+    }
+    pub fn next_accepts(&self) -> &BTreeMap<StateRef, Vec<Option<Output>>> {
+        &self.next_accepts
+    }
+    pub(crate) fn byte_classes(&self) -> ByteClasses {
+        ByteClasses::new(
+            self.states
+                .iter()
+                .flat_map(|state| state.transitions.iter().map(|t| &t.when)),
+        )
+    }
     fn new() -> Self {
         Self {
             start: StateRef(0),
-            accepts: HashMap::new(),
+            accepts: BTreeMap::new(),
             states: vec![State::new()],
+            starts: vec![],
+            next_accepts: BTreeMap::new(),
         }
     }
     fn add(&mut self) -> StateRef {
@@ -189,22 +361,10 @@ impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
         self.accepts.insert(node, None);
     }
     fn set_accept_output(&mut self, node: StateRef, output: Option<Output>) -> Result<(), Error> {
-        if let Some(ref output) = output {
-            if let Some(Some(current_output)) = self.accepts.get(&node) {
-                match current_output.priority.cmp(&output.priority) {
-                    Ordering::Less => {}
-                    Ordering::Equal => {
-                        return Err(format!(
-                            "tokens `{}` and `{}` both have priority {} and may match the same word",
-                            current_output.value.0, output.value.0, current_output.priority,
-                        )
-                        .into());
-                    }
-                    Ordering::Greater => return Ok(()),
-                }
-            }
+        let current_output = self.accepts.get(&node).cloned().flatten();
+        if let Some(output) = Output::select(current_output, output)? {
+            self.accepts.insert(node, output);
         }
-        self.accepts.insert(node, output);
         Ok(())
     }
     fn append(&mut self, other: Nfa) -> StateRef {
@@ -213,6 +373,9 @@ impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
             for t in state.transitions.iter_mut() {
                 t.to.0 += offset;
             }
+            for (_, to) in state.looks.iter_mut() {
+                to.0 += offset;
+            }
             self.states.push(state);
         }
         for (node, tok) in other.accepts {
@@ -228,6 +391,53 @@ impl Token {
             nfa,
             priority,
             value,
+            end: TokenEnd::MatchEnd,
+            name: None,
         }
     }
+    pub fn nfa(&self) -> &Nfa {
+        &self.nfa
+    }
+    pub fn value(&self) -> &(String, usize) {
+        &self.value
+    }
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.value.0)
+    }
+    pub fn priority(&self) -> usize {
+        self.priority
+    }
+    /// Replaces the value of the token, e.g. to tell apart the regexes of a value in an analysis.
+    pub fn with_value(mut self, value: (String, usize)) -> Self {
+        self.value = value;
+        self
+    }
+    /// Sets the name of the token in diagnostics.
+    ///
+    /// Tokens with different names must have different values.
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+    /// Adds trailing context, so the token only matches if it is followed by a word of the
+    /// lookahead, which is not part of the token.
+    ///
+    /// The end of the token can only be determined, if either the token or the lookahead only
+    /// match words of a fixed length.
+    pub fn with_lookahead(mut self, lookahead: Nfa) -> Result<Self, Error> {
+        if lookahead.accepts_empty() {
+            return Err("lookahead regex matches empty word".into());
+        }
+        self.end = if let Some(len) = lookahead.fixed_length() {
+            TokenEnd::BeforeMatchEnd(len)
+        } else if let Some(len) = self.nfa.fixed_length() {
+            TokenEnd::AfterMatchStart(len)
+        } else {
+            return Err(
+                "either the token or the lookahead must only match words of a fixed length".into(),
+            );
+        };
+        self.nfa.concat(lookahead);
+        Ok(self)
+    }
 }
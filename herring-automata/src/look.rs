@@ -0,0 +1,101 @@
+use crate::Pattern;
+use regex_syntax::hir::{ClassBytes, ClassBytesRange, Look};
+
+/// Kind of the byte before or after a position, which decides if a look-around assertion holds.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ByteKind {
+    /// There is no byte at the start or end of the input.
+    Boundary,
+    LineFeed,
+    CarriageReturn,
+    /// ASCII word character `[0-9A-Za-z_]`
+    Word,
+    /// Non-ASCII bytes are treated as word characters for unicode word boundaries.
+    NonAscii,
+    Other,
+}
+
+impl ByteKind {
+    pub const ALL: [ByteKind; 6] = [
+        ByteKind::Boundary,
+        ByteKind::LineFeed,
+        ByteKind::CarriageReturn,
+        ByteKind::Word,
+        ByteKind::NonAscii,
+        ByteKind::Other,
+    ];
+
+    pub fn of(b: Option<u8>) -> Self {
+        match b {
+            None => ByteKind::Boundary,
+            Some(b'\n') => ByteKind::LineFeed,
+            Some(b'\r') => ByteKind::CarriageReturn,
+            Some(b) if b.is_ascii_alphanumeric() || b == b'_' => ByteKind::Word,
+            Some(0x80..=0xFF) => ByteKind::NonAscii,
+            Some(_) => ByteKind::Other,
+        }
+    }
+
+    /// Returns the bytes of this kind, which is `None` for the boundary.
+    pub fn pattern(self) -> Option<Pattern> {
+        let ranges = match self {
+            ByteKind::Boundary => return None,
+            ByteKind::LineFeed => vec![(b'\n', b'\n')],
+            ByteKind::CarriageReturn => vec![(b'\r', b'\r')],
+            ByteKind::Word => vec![(b'0', b'9'), (b'A', b'Z'), (b'_', b'_'), (b'a', b'z')],
+            ByteKind::NonAscii => vec![(0x80, 0xFF)],
+            ByteKind::Other => {
+                let mut class = ClassBytes::new(
+                    ByteKind::ALL
+                        .iter()
+                        .filter(|kind| **kind != ByteKind::Other)
+                        .filter_map(|kind| kind.pattern())
+                        .flat_map(|pattern| pattern.0.ranges().to_vec()),
+                );
+                class.negate();
+                return Some(Pattern::from_class(class));
+            }
+        };
+        Some(Pattern::from_class(ClassBytes::new(
+            ranges
+                .into_iter()
+                .map(|(start, end)| ClassBytesRange::new(start, end)),
+        )))
+    }
+
+    fn is_word(self, unicode: bool) -> bool {
+        self == ByteKind::Word || (unicode && self == ByteKind::NonAscii)
+    }
+}
+
+/// Checks if the assertion holds between bytes of the given kinds.
+///
+/// Unicode word boundaries are approximated by treating all non-ASCII characters as word
+/// characters, as the automaton only knows the kind of a single byte.
+pub(crate) fn look_matches(look: Look, prev: ByteKind, next: ByteKind) -> bool {
+    use ByteKind::*;
+    let is_line_start = matches!(prev, Boundary | LineFeed);
+    match look {
+        Look::Start => prev == Boundary,
+        Look::End => next == Boundary,
+        Look::StartLF => is_line_start,
+        Look::EndLF => matches!(next, Boundary | LineFeed),
+        Look::StartCRLF => is_line_start || (prev == CarriageReturn && next != LineFeed),
+        Look::EndCRLF => {
+            matches!(next, Boundary | CarriageReturn)
+                || (next == LineFeed && prev != CarriageReturn)
+        }
+        Look::WordAscii => prev.is_word(false) != next.is_word(false),
+        Look::WordAsciiNegate => prev.is_word(false) == next.is_word(false),
+        Look::WordUnicode => prev.is_word(true) != next.is_word(true),
+        Look::WordUnicodeNegate => prev.is_word(true) == next.is_word(true),
+        Look::WordStartAscii => !prev.is_word(false) && next.is_word(false),
+        Look::WordEndAscii => prev.is_word(false) && !next.is_word(false),
+        Look::WordStartUnicode => !prev.is_word(true) && next.is_word(true),
+        Look::WordEndUnicode => prev.is_word(true) && !next.is_word(true),
+        Look::WordStartHalfAscii => !prev.is_word(false),
+        Look::WordEndHalfAscii => !next.is_word(false),
+        Look::WordStartHalfUnicode => !prev.is_word(true),
+        Look::WordEndHalfUnicode => !next.is_word(true),
+    }
+}
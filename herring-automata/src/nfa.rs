@@ -1,14 +1,25 @@
-use crate::{Dfa, Error, Nfa, Output, Pattern, StateRef, Token};
-use regex_syntax::hir::{Class, ClassUnicode, Hir, HirKind};
+use crate::fold::{ascii_case_fold, full_case_edges, full_case_fold};
+use crate::look::look_matches;
+use crate::subpattern::{is_complement_group, Expansion};
+use crate::{
+    ByteClasses, ByteKind, CaseFolding, Dfa, Error, Location, Nfa, Output, Pattern, StateRef,
+    Subpattern, Token,
+};
+use regex_syntax::hir::{Class, ClassBytes, ClassBytesRange, ClassUnicode, Hir, HirKind, Look};
 use regex_syntax::utf8::{Utf8Range, Utf8Sequence, Utf8Sequences};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 impl Nfa {
     pub fn new_tokenizer(token_regexes: Vec<Token>) -> Nfa {
         let mut automaton = Nfa::new();
         for mut token_regex in token_regexes.into_iter() {
             for accept in token_regex.nfa.accepts.iter_mut() {
-                *accept.1 = Some(Output::new(token_regex.priority, token_regex.value.clone()));
+                *accept.1 = Some(Output::new(
+                    token_regex.priority,
+                    token_regex.value.clone(),
+                    token_regex.end,
+                    token_regex.name.clone(),
+                ));
             }
             let s = automaton.append(token_regex.nfa);
             automaton.add_epsilon_transition(automaton.start, s);
@@ -18,7 +29,7 @@ impl Nfa {
 
     pub fn accepts_empty(&self) -> bool {
         let mut start_set = BTreeSet::from_iter([self.start]);
-        self.epsilon_closure(&mut start_set);
+        self.look_closure(&mut start_set, |_| true);
         for state in start_set {
             if self.accepts.contains_key(&state) {
                 return true;
@@ -27,6 +38,176 @@ impl Nfa {
         false
     }
 
+    /// Returns the length in bytes of the accepted words, if all of them have the same length.
+    pub fn fixed_length(&self) -> Option<usize> {
+        let mut length = None;
+        let mut state_set = BTreeSet::from_iter([self.start]);
+        self.look_closure(&mut state_set, |_| true);
+        // a path longer than the number of states must contain a cycle
+        for len in 0..=self.states.len() {
+            if state_set.is_empty() {
+                return length;
+            }
+            if state_set
+                .iter()
+                .any(|state| self.accepts.contains_key(state))
+            {
+                if length.is_some() {
+                    return None;
+                }
+                length = Some(len);
+            }
+            let mut next_set = BTreeSet::new();
+            for state in state_set.iter() {
+                for t in self.states[state.0].transitions.iter() {
+                    if !t.when.is_empty() {
+                        next_set.insert(t.to);
+                    }
+                }
+            }
+            self.look_closure(&mut next_set, |_| true);
+            state_set = next_set;
+        }
+        None
+    }
+
+    /// Splits a regex of the form `r(?=s)` into `r` and the lookahead `s`.
+    ///
+    /// The lookahead must end a concatenation at the top level of the regex, so it applies to
+    /// every word of `r`. A lookahead after a top-level alternation or inside a group is an error.
+    pub fn split_lookahead(regex: &str) -> Result<(&str, Option<&str>), Error> {
+        let lookahead_error = |message: &str, range: std::ops::Range<usize>| Error {
+            message: message.to_string(),
+            conflict: None,
+            location: Some(Location {
+                subpattern: None,
+                range,
+            }),
+        };
+        let mut group_starts = vec![];
+        let mut alternation = false;
+        let mut lookahead = None;
+        let mut in_class = 0;
+        let mut chars = regex.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '[' => {
+                    in_class += 1;
+                    // a closing bracket at the start of a class is a literal
+                    if let Some((_, '^')) = chars.clone().next() {
+                        chars.next();
+                    }
+                    if let Some((_, ']')) = chars.clone().next() {
+                        chars.next();
+                    }
+                }
+                ']' if in_class > 0 => in_class -= 1,
+                '|' if in_class == 0 && group_starts.is_empty() => alternation = true,
+                '(' if in_class == 0 => group_starts.push(i),
+                ')' if in_class == 0 => {
+                    if let Some(start) = group_starts.pop() {
+                        if regex[start..].starts_with("(?=") {
+                            if !group_starts.is_empty() || i + 1 != regex.len() {
+                                return Err(lookahead_error(
+                                    "lookahead is only supported at the end of a regex",
+                                    start..i + 1,
+                                ));
+                            }
+                            lookahead = Some(start);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        match lookahead {
+            Some(start) if alternation => Err(lookahead_error(
+                "lookahead after an alternation would apply to all of its branches (use a group like `(a|b)(?=c)` or a lookahead in a separate token)",
+                start..regex.len(),
+            )),
+            Some(start) => Ok((&regex[..start], Some(&regex[start + 3..regex.len() - 1]))),
+            None => Ok((regex, None)),
+        }
+    }
+
+    /// Explains how the priority of a regex is derived by `hir_priority`.
+    pub fn explain_regex_priority(
+        regex: &str,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        case_folding: CaseFolding,
+        binary: bool,
+    ) -> Result<String, Error> {
+        let hir = Self::parse_regex(regex, subpatterns, case_folding, binary)?;
+        Ok(format!(
+            "{} = {}",
+            Self::hir_priority(&hir),
+            Self::explain_hir_priority(&hir)
+        ))
+    }
+
+    /// Explains how the priority of a token is derived by `hir_priority`.
+    pub fn explain_token_priority(token: &str, case_folding: CaseFolding) -> String {
+        Self::explain_regex_priority(
+            &regex_syntax::escape(token),
+            &BTreeMap::new(),
+            case_folding,
+            false,
+        )
+        .unwrap()
+    }
+
+    fn explain_hir_priority(hir: &Hir) -> String {
+        let pattern = hir
+            .to_string()
+            .chars()
+            .map(|c| {
+                if c.is_control() {
+                    c.escape_debug().to_string()
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect::<String>();
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => format!("0 for `{pattern}`"),
+            HirKind::Literal(literal) => {
+                let count = match std::str::from_utf8(&literal.0) {
+                    Ok(s) => s.chars().count(),
+                    Err(_) => literal.0.len(),
+                };
+                format!("2 × {count} for `{pattern}`")
+            }
+            HirKind::Class(_) => format!("2 for `{pattern}`"),
+            HirKind::Capture(capture)
+                if capture.name.as_deref().is_some_and(is_complement_group) =>
+            {
+                "0 for a complement".to_string()
+            }
+            HirKind::Capture(capture) => Self::explain_hir_priority(&capture.sub),
+            HirKind::Concat(concat) => concat
+                .iter()
+                .map(Self::explain_hir_priority)
+                .collect::<Vec<_>>()
+                .join(" + "),
+            HirKind::Alternation(alternation) => format!(
+                "min({})",
+                alternation
+                    .iter()
+                    .map(Self::explain_hir_priority)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            HirKind::Repetition(repetition) => format!(
+                "{} × ({})",
+                repetition.min,
+                Self::explain_hir_priority(&repetition.sub)
+            ),
+        }
+    }
+
     /// Regex priority heuristic used by Logos
     fn hir_priority(hir: &Hir) -> usize {
         match hir.kind() {
@@ -36,6 +217,11 @@ impl Nfa {
                 Err(_) => 2 * literal.0.len(),
             },
             HirKind::Class(_) => 2,
+            HirKind::Capture(capture)
+                if capture.name.as_deref().is_some_and(is_complement_group) =>
+            {
+                0
+            }
             HirKind::Capture(capture) => Self::hir_priority(&capture.sub),
             HirKind::Concat(concat) => concat.iter().map(Self::hir_priority).sum(),
             HirKind::Alternation(alternation) => alternation
@@ -49,76 +235,156 @@ impl Nfa {
         }
     }
 
-    fn replace_subpatterns(
-        mut value: String,
-        subpatterns: &HashMap<String, String>,
-    ) -> Result<String, Error> {
-        const MAX_DEPTH: usize = 128;
-        for i in 0..MAX_DEPTH {
-            let mut replaced = false;
-            for (name, pattern) in subpatterns {
-                let pattern_ref = format!("(?&{name})");
-                let pattern = format!("({pattern})");
-                if value.contains(&pattern_ref) {
-                    replaced = true;
-                    value = value.replace(&pattern_ref, &pattern);
-                }
-            }
-            if !replaced {
-                break;
-            }
-            if i == MAX_DEPTH - 1 {
-                return Err(format!(
-                    "exceeded maximum subpattern replacement depth of {MAX_DEPTH}"
-                )
-                .into());
-            }
-        }
-        if let Some(start) = value.find("(?&") {
-            if let Some(end) = value[start..].find(')') {
-                return Err(format!(
-                    "use of undefined subpattern `{}`",
-                    &value[start + 3..start + end]
-                )
-                .into());
-            }
-            return Err("use of undefined subpattern".into());
-        }
-        Ok(value)
-    }
-
     pub fn from_regex_with_subpatterns(
         regex: &str,
-        subpatterns: &HashMap<String, String>,
-        ignore_case: bool,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        case_folding: CaseFolding,
         binary: bool,
     ) -> Result<(Nfa, usize), Error> {
-        let subpattern_replaced_regex = Self::replace_subpatterns(regex.to_string(), subpatterns)?;
-        Self::from_regex(&subpattern_replaced_regex, ignore_case, binary)
+        let hir = Self::parse_regex(regex, subpatterns, case_folding, binary)?;
+        Self::from_parsed_regex(hir, case_folding, binary)
+    }
+
+    /// Returns the case folding, which is not applied by the regex parser and must be applied
+    /// when constructing the automaton.
+    ///
+    /// Full case folding is only applied to text, so it is simple case folding for binary regexes.
+    pub(crate) fn hir_case_folding(case_folding: CaseFolding, binary: bool) -> CaseFolding {
+        match case_folding {
+            CaseFolding::Full if !binary => CaseFolding::Full,
+            CaseFolding::Ascii => CaseFolding::Ascii,
+            _ => CaseFolding::Disabled,
+        }
     }
 
-    pub fn from_regex(regex: &str, ignore_case: bool, binary: bool) -> Result<(Nfa, usize), Error> {
-        let hir = regex_syntax::ParserBuilder::new()
+    /// Parses the regex after replacing its subpatterns, where literals and classes are not folded
+    /// for full and ASCII case folding, as they are folded by `from_hir`.
+    ///
+    /// Syntax errors are located in the regex or the subpattern containing them.
+    pub(crate) fn parse_regex(
+        regex: &str,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        case_folding: CaseFolding,
+        binary: bool,
+    ) -> Result<Hir, Error> {
+        let expansion = Expansion::new(regex, subpatterns)?;
+        let parser_folding = case_folding != CaseFolding::Disabled
+            && Self::hir_case_folding(case_folding, binary) == CaseFolding::Disabled;
+        regex_syntax::ParserBuilder::new()
             .utf8(!binary)
             .unicode(!binary)
-            .case_insensitive(ignore_case)
+            .multi_line(true)
+            .case_insensitive(parser_folding)
             .build()
-            .parse(regex)?;
+            .parse(&expansion.regex)
+            .map_err(|err| expansion.syntax_error(err))
+    }
+
+    pub fn from_regex(
+        regex: &str,
+        case_folding: CaseFolding,
+        binary: bool,
+    ) -> Result<(Nfa, usize), Error> {
+        let hir = Self::parse_regex(regex, &BTreeMap::new(), case_folding, binary)?;
+        Self::from_parsed_regex(hir, case_folding, binary)
+    }
+
+    fn from_parsed_regex(
+        hir: Hir,
+        case_folding: CaseFolding,
+        binary: bool,
+    ) -> Result<(Nfa, usize), Error> {
         let priority = Self::hir_priority(&hir);
-        Ok((Self::from_hir(hir)?, priority))
+        let mut lazy = false;
+        Self::check_lazy_repetitions(&hir, &mut lazy)?;
+        let automaton = Self::from_hir(hir, Self::hir_case_folding(case_folding, binary), binary)?;
+        if lazy {
+            return Ok((automaton.into_shortest()?, priority));
+        }
+        Ok((automaton, priority))
     }
 
-    pub fn from_token(token: &str, ignore_case: bool) -> (Nfa, usize) {
-        Self::from_regex(&regex_syntax::escape(token), ignore_case, false).unwrap()
+    /// Non-greedy repetitions are implemented by matching the shortest word, so they cannot be
+    /// combined with greedy repetitions of variable length.
+    fn check_lazy_repetitions(hir: &Hir, lazy: &mut bool) -> Result<(), Error> {
+        fn visit(hir: &Hir, lazy: &mut bool, greedy: &mut bool) {
+            match hir.kind() {
+                HirKind::Repetition(repetition) => {
+                    if repetition.greedy {
+                        *greedy |= repetition.max != Some(repetition.min);
+                    } else {
+                        *lazy = true;
+                    }
+                    visit(&repetition.sub, lazy, greedy);
+                }
+                HirKind::Capture(capture) => visit(&capture.sub, lazy, greedy),
+                HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+                    for hir in hirs {
+                        visit(hir, lazy, greedy);
+                    }
+                }
+                HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => {}
+            }
+        }
+        let mut greedy = false;
+        visit(hir, lazy, &mut greedy);
+        if *lazy && greedy {
+            return Err(
+                "non-greedy repetitions cannot be combined with greedy repetitions of variable length"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Converts both automata to DFAs and combines them with the given operation.
+    fn combine(self, other: Nfa, op: impl Fn(&Dfa, &Dfa) -> Dfa) -> Result<Nfa, Error> {
+        let (a, b) = (self.into_dfa()?, other.into_dfa()?);
+        for dfa in [&a, &b] {
+            if !dfa.starts.is_empty() || !dfa.next_accepts.is_empty() {
+                return Err(
+                    "intersection and difference are not supported for regexes with anchors".into(),
+                );
+            }
+        }
+        op(&a, &b).into_nfa()
     }
 
-    pub fn from_bytes(bytes: &[u8], ignore_case: bool) -> Nfa {
+    /// Accepts the words accepted by both automata.
+    pub fn intersection(self, other: Nfa) -> Result<Nfa, Error> {
+        self.combine(other, Dfa::intersection)
+    }
+
+    /// Accepts the words accepted by this automaton, but not by the other one.
+    pub fn difference(self, other: Nfa) -> Result<Nfa, Error> {
+        self.combine(other, Dfa::difference)
+    }
+
+    /// Restricts the automaton to the words, which do not have an accepted proper prefix, so a
+    /// token ends as soon as it reaches acceptance for the first time.
+    pub fn into_shortest(self) -> Result<Nfa, Error> {
+        let mut dfa = self.into_dfa()?;
+        if !dfa.starts.is_empty() || !dfa.next_accepts.is_empty() {
+            return Err("shortest match is not supported for regexes with anchors".into());
+        }
+        for accept in dfa.accepts.keys() {
+            dfa.states[accept.0].transitions.clear();
+        }
+        dfa.into_nfa()
+    }
+
+    pub fn from_token(token: &str, case_folding: CaseFolding) -> (Nfa, usize) {
+        Self::from_regex(&regex_syntax::escape(token), case_folding, false).unwrap()
+    }
+
+    /// Any case folding of bytes only folds the ASCII letters.
+    pub fn from_bytes(bytes: &[u8], case_folding: CaseFolding) -> Nfa {
         let mut automaton = Nfa::new();
         let mut last_node = automaton.start;
         for b in bytes {
             let next = automaton.add();
             let mut pattern = Pattern::from_byte(*b);
-            if ignore_case {
+            if case_folding != CaseFolding::Disabled {
                 pattern.0.case_fold_simple();
             }
             automaton.add_transition(last_node, pattern, next);
@@ -128,8 +394,44 @@ impl Nfa {
         automaton
     }
 
+    /// Matches all words with the same full case folding as the literal.
+    fn from_full_case_literal(literal: &str) -> Nfa {
+        let folded = full_case_fold(literal);
+        let mut automaton = Nfa::new();
+        let mut positions = vec![automaton.start];
+        for _ in 0..folded.len() {
+            positions.push(automaton.add());
+        }
+        for pos in 0..folded.len() {
+            for (class, next) in full_case_edges(&folded, pos) {
+                let class_automaton = Self::from_unicode_class(class);
+                automaton.append_between(positions[pos], class_automaton, positions[next]);
+            }
+        }
+        automaton.set_accept(positions[folded.len()]);
+        automaton
+    }
+
+    /// Appends the automaton, so it connects the given states.
+    fn append_between(&mut self, from: StateRef, other: Nfa, to: StateRef) {
+        let offset = self.states.len();
+        let accepts = other
+            .accepts
+            .keys()
+            .map(|node| StateRef(node.0 + offset))
+            .collect::<Vec<_>>();
+        let s = self.append(other);
+        self.add_epsilon_transition(from, s);
+        for node in accepts {
+            self.accepts.remove(&node);
+            self.add_epsilon_transition(node, to);
+        }
+    }
+
     /// Extended Thompson's construction
-    fn from_hir(hir: Hir) -> Result<Nfa, Error> {
+    ///
+    /// Literals and classes are folded for full and ASCII case folding.
+    fn from_hir(hir: Hir, case_folding: CaseFolding, binary: bool) -> Result<Nfa, Error> {
         match hir.into_kind() {
             HirKind::Empty => {
                 let mut automaton = Nfa::new();
@@ -138,10 +440,26 @@ impl Nfa {
                 automaton.add_epsilon_transition(start, end);
                 Ok(automaton)
             }
-            HirKind::Literal(literal) => Ok(Self::from_bytes(&literal.0, false)),
+            HirKind::Literal(literal) => Ok(match std::str::from_utf8(&literal.0) {
+                Ok(literal) if case_folding == CaseFolding::Full => {
+                    Self::from_full_case_literal(literal)
+                }
+                _ => Self::from_bytes(&literal.0, case_folding),
+            }),
             HirKind::Class(class) => Ok(match class {
-                Class::Unicode(class) => Self::from_unicode_class(class),
-                Class::Bytes(class) => {
+                Class::Unicode(mut class) => {
+                    match case_folding {
+                        CaseFolding::Full => class.case_fold_simple(),
+                        CaseFolding::Ascii => ascii_case_fold(&mut class),
+                        _ => {}
+                    }
+                    Self::from_unicode_class(class)
+                }
+                Class::Bytes(mut class) => {
+                    // only ASCII letters are folded in byte classes
+                    if case_folding != CaseFolding::Disabled {
+                        class.case_fold_simple();
+                    }
                     let mut automaton = Nfa::new();
                     let start = automaton.start;
                     let end = automaton.add_accept();
@@ -149,95 +467,107 @@ impl Nfa {
                     automaton
                 }
             }),
-            HirKind::Look(_) => Err("Herring does not support look-around".into()),
-            HirKind::Repetition(repetition) => {
-                if !repetition.greedy {
-                    return Err("Herring does not support non-greedy repetitions".into());
+            HirKind::Look(look) => {
+                let mut automaton = Nfa::new();
+                let start = automaton.start;
+                let end = automaton.add_accept();
+                automaton.states[start.0].looks.push((look, end));
+                Ok(automaton)
+            }
+            HirKind::Repetition(repetition) => Ok(match (repetition.min, repetition.max) {
+                (0, Some(1)) => {
+                    let mut automaton = Self::from_hir(*repetition.sub, case_folding, binary)?;
+                    let accepts = automaton.accepts.keys().copied().collect::<Vec<_>>();
+                    for node in accepts {
+                        automaton.add_epsilon_transition(automaton.start, node);
+                    }
+                    automaton
                 }
-                Ok(match (repetition.min, repetition.max) {
-                    (0, Some(1)) => {
-                        let mut automaton = Self::from_hir(*repetition.sub)?;
-                        let accepts = automaton.accepts.keys().copied().collect::<Vec<_>>();
-                        for node in accepts {
-                            automaton.add_epsilon_transition(automaton.start, node);
-                        }
-                        automaton
+                (0, None) => {
+                    let mut inner = Self::from_hir(*repetition.sub, case_folding, binary)?;
+                    let inner_accepts = inner.accepts.keys().copied().collect::<Vec<_>>();
+                    for node in inner_accepts {
+                        inner.add_epsilon_transition(node, inner.start);
                     }
-                    (0, None) => {
-                        let mut inner = Self::from_hir(*repetition.sub)?;
-                        let inner_accepts = inner.accepts.keys().copied().collect::<Vec<_>>();
-                        for node in inner_accepts {
-                            inner.add_epsilon_transition(node, inner.start);
-                        }
 
-                        let mut automaton = Nfa::new();
-                        automaton.set_accept(automaton.start);
-                        automaton.concat(inner);
-                        let accepts = std::mem::take(&mut automaton.accepts);
-                        let end = automaton.add_accept();
-                        for (node, _) in accepts {
-                            automaton.add_epsilon_transition(node, end);
-                        }
-                        automaton.add_epsilon_transition(automaton.start, end);
-                        automaton
+                    let mut automaton = Nfa::new();
+                    automaton.set_accept(automaton.start);
+                    automaton.concat(inner);
+                    let accepts = std::mem::take(&mut automaton.accepts);
+                    let end = automaton.add_accept();
+                    for (node, _) in accepts {
+                        automaton.add_epsilon_transition(node, end);
                     }
-                    (1, None) => {
-                        let mut automaton = Self::from_hir(*repetition.sub)?;
-                        let accepts = automaton.accepts.keys().copied().collect::<Vec<_>>();
-                        for node in accepts {
-                            automaton.add_epsilon_transition(node, automaton.start);
+                    automaton.add_epsilon_transition(automaton.start, end);
+                    automaton
+                }
+                (1, None) => {
+                    let mut automaton = Self::from_hir(*repetition.sub, case_folding, binary)?;
+                    let accepts = automaton.accepts.keys().copied().collect::<Vec<_>>();
+                    for node in accepts {
+                        automaton.add_epsilon_transition(node, automaton.start);
+                    }
+                    automaton
+                }
+                (n, m) => {
+                    // the characters of a literal may have multi-byte case variants
+                    let byte_class = Self::byte_class(&repetition.sub)
+                        .filter(|_| case_folding != CaseFolding::Full);
+                    if let Some(mut class) = byte_class {
+                        if case_folding == CaseFolding::Ascii {
+                            class.case_fold_simple();
                         }
-                        automaton
+                        return Ok(Self::from_byte_class_repetition(class, n, m));
+                    }
+                    let inner = Self::from_hir(*repetition.sub, case_folding, binary)?;
+                    let mut automaton = if n > 0 {
+                        inner.clone()
+                    } else {
+                        let mut empty = Nfa::new();
+                        empty.set_accept(empty.start);
+                        empty
+                    };
+                    for _ in 1..n {
+                        automaton.concat(inner.clone());
                     }
-                    (n, m) => {
-                        let inner = Self::from_hir(*repetition.sub)?;
-                        let mut automaton = if n > 0 {
-                            inner.clone()
-                        } else {
-                            let mut empty = Nfa::new();
-                            empty.set_accept(empty.start);
-                            empty
-                        };
-                        for _ in 1..n {
-                            automaton.concat(inner.clone());
+                    if let Some(m) = m {
+                        if m > n {
+                            automaton.concat(Self::nested_optional(inner, m - n));
                         }
-                        if let Some(m) = m {
-                            if m > n {
-                                let mut maybe = inner.clone();
-                                let accepts = maybe.accepts.keys().copied().collect::<Vec<_>>();
-                                for node in accepts {
-                                    maybe.add_epsilon_transition(maybe.start, node);
-                                }
-                                for _ in n..m {
-                                    automaton.concat(maybe.clone());
-                                }
-                            }
-                        } else {
-                            let mut repeat = inner.clone();
-                            let accepts = repeat.accepts.keys().copied().collect::<Vec<_>>();
-                            for node in accepts {
-                                repeat.add_epsilon_transition(repeat.start, node);
-                                repeat.add_epsilon_transition(node, repeat.start);
-                            }
-                            automaton.concat(repeat);
+                    } else {
+                        let mut repeat = inner.clone();
+                        let accepts = repeat.accepts.keys().copied().collect::<Vec<_>>();
+                        for node in accepts {
+                            repeat.add_epsilon_transition(repeat.start, node);
+                            repeat.add_epsilon_transition(node, repeat.start);
                         }
-                        automaton
+                        automaton.concat(repeat);
                     }
-                })
+                    automaton
+                }
+            }),
+            HirKind::Capture(capture)
+                if capture.name.as_deref().is_some_and(is_complement_group) =>
+            {
+                let inner = Self::from_hir(*capture.sub, case_folding, binary)?;
+                let any = if binary { "(?s-u:.)*" } else { "(?s:.)*" };
+                Self::from_regex(any, CaseFolding::Disabled, binary)?
+                    .0
+                    .difference(inner)
             }
-            HirKind::Capture(capture) => Self::from_hir(*capture.sub),
+            HirKind::Capture(capture) => Self::from_hir(*capture.sub, case_folding, binary),
             HirKind::Concat(concat) => {
                 let mut it = concat.into_iter();
-                let mut automaton = Self::from_hir(it.next().unwrap())?;
+                let mut automaton = Self::from_hir(it.next().unwrap(), case_folding, binary)?;
                 for a in it {
-                    automaton.concat(Self::from_hir(a)?);
+                    automaton.concat(Self::from_hir(a, case_folding, binary)?);
                 }
                 Ok(automaton)
             }
             HirKind::Alternation(alternation) => {
                 let mut automaton = Nfa::new();
                 for a in alternation.into_iter() {
-                    let s = automaton.append(Self::from_hir(a)?);
+                    let s = automaton.append(Self::from_hir(a, case_folding, binary)?);
                     automaton.add_epsilon_transition(automaton.start, s);
                 }
                 let old_accepts = std::mem::take(&mut automaton.accepts);
@@ -251,7 +581,63 @@ impl Nfa {
         }
     }
 
-    fn concat(&mut self, other: Nfa) {
+    fn byte_class(hir: &Hir) -> Option<ClassBytes> {
+        match hir.kind() {
+            HirKind::Class(Class::Bytes(class)) => Some(class.clone()),
+            HirKind::Class(Class::Unicode(class)) => class.to_byte_class(),
+            HirKind::Literal(literal) if literal.0.len() == 1 => {
+                Some(ClassBytes::new([ClassBytesRange::new(
+                    literal.0[0],
+                    literal.0[0],
+                )]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Repetition of a byte class with one state per repetition
+    fn from_byte_class_repetition(class: ClassBytes, min: u32, max: Option<u32>) -> Nfa {
+        let mut automaton = Nfa::new();
+        let mut last_node = automaton.start;
+        if min == 0 {
+            automaton.set_accept(last_node);
+        }
+        for i in 1..=max.unwrap_or(min) {
+            let next = automaton.add();
+            automaton.add_transition(last_node, Pattern::from_class(class.clone()), next);
+            if i >= min {
+                automaton.set_accept(next);
+            }
+            last_node = next;
+        }
+        if max.is_none() {
+            automaton.add_transition(last_node, Pattern::from_class(class), last_node);
+        }
+        automaton
+    }
+
+    /// Constructs `(x(x(x)?)?)?` instead of `x?x?x?`, so a state set of the subset construction
+    /// does not contain the start states of all remaining optional copies.
+    fn nested_optional(inner: Nfa, count: u32) -> Nfa {
+        let mut automaton = Nfa::new();
+        let end = automaton.add();
+        let mut last_node = automaton.start;
+        for _ in 0..count {
+            automaton.add_epsilon_transition(last_node, end);
+            let s = automaton.append(inner.clone());
+            automaton.add_epsilon_transition(last_node, s);
+            let accepts = std::mem::take(&mut automaton.accepts);
+            last_node = automaton.add();
+            for (node, _) in accepts {
+                automaton.add_epsilon_transition(node, last_node);
+            }
+        }
+        automaton.add_epsilon_transition(last_node, end);
+        automaton.set_accept(end);
+        automaton
+    }
+
+    pub(crate) fn concat(&mut self, other: Nfa) {
         let old_accepts = std::mem::take(&mut self.accepts);
         let s = self.append(other);
         for (node, _) in old_accepts {
@@ -327,22 +713,110 @@ impl Nfa {
     }
 
     fn epsilon_closure(&self, state_set: &mut BTreeSet<StateRef>) {
-        let mut size = state_set.len();
-        loop {
-            let mut new_states = vec![];
-            for state in state_set.iter() {
-                for transition in self.states[state.0].transitions.iter() {
-                    if transition.when.is_empty() {
-                        new_states.push(transition.to);
-                    }
+        self.look_closure(state_set, |_| false);
+    }
+
+    /// Epsilon closure, which also follows the look-around assertions that hold.
+    fn look_closure(&self, state_set: &mut BTreeSet<StateRef>, holds: impl Fn(Look) -> bool) {
+        let mut todo = state_set.iter().copied().collect::<Vec<_>>();
+        while let Some(state) = todo.pop() {
+            let state = &self.states[state.0];
+            let epsilons = state
+                .transitions
+                .iter()
+                .filter(|t| t.when.is_empty())
+                .map(|t| t.to);
+            let looks = state
+                .looks
+                .iter()
+                .filter(|(look, _)| holds(*look))
+                .map(|(_, to)| *to);
+            for to in epsilons.chain(looks) {
+                if state_set.insert(to) {
+                    todo.push(to);
                 }
             }
-            state_set.extend(new_states);
-            if size == state_set.len() {
-                break;
+        }
+    }
+
+    /// Returns a shortest word accepted by both automata, where look-around assertions are
+    /// assumed to hold.
+    pub fn shortest_common_word(&self, other: &Nfa) -> Option<Vec<u8>> {
+        let closure = |nfa: &Nfa, mut set: BTreeSet<StateRef>| {
+            nfa.look_closure(&mut set, |_| true);
+            set
+        };
+        let byte_classes = ByteClasses::new(
+            self.states
+                .iter()
+                .chain(other.states.iter())
+                .flat_map(|state| state.transitions.iter().map(|t| &t.when)),
+        );
+        let start = (
+            closure(self, BTreeSet::from_iter([self.start])),
+            closure(other, BTreeSet::from_iter([other.start])),
+        );
+        let mut visited = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([(start, vec![])]);
+        while let Some(((set, other_set), word)) = queue.pop_front() {
+            if set.iter().any(|state| self.accepts.contains_key(state))
+                && other_set
+                    .iter()
+                    .any(|state| other.accepts.contains_key(state))
+            {
+                return Some(word);
             }
-            size = state_set.len();
+            for (b, _) in byte_classes.iter() {
+                let next = (
+                    closure(self, self.move_set(&set, b)),
+                    closure(other, other.move_set(&other_set, b)),
+                );
+                if next.0.is_empty() || next.1.is_empty() || !visited.insert(next.clone()) {
+                    continue;
+                }
+                let mut next_word = word.clone();
+                next_word.push(b);
+                queue.push_back((next, next_word));
+            }
+        }
+        None
+    }
+
+    fn has_looks(&self, state_set: &BTreeSet<StateRef>) -> bool {
+        state_set
+            .iter()
+            .any(|state| !self.states[state.0].looks.is_empty())
+    }
+
+    /// Resolves the look-around assertions of a state set between bytes of the given kinds.
+    fn resolve_looks(
+        &self,
+        state_set: &BTreeSet<StateRef>,
+        prev: Option<ByteKind>,
+        next: ByteKind,
+    ) -> BTreeSet<StateRef> {
+        let mut set = state_set.clone();
+        if let Some(prev) = prev {
+            self.look_closure(&mut set, |look| look_matches(look, prev, next));
         }
+        set
+    }
+
+    /// Returns the output of the highest priority, if the state set is accepting.
+    fn accept_output(
+        &self,
+        state_set: &BTreeSet<StateRef>,
+    ) -> Result<Option<Option<Output>>, Error> {
+        let mut accept_output = None;
+        for (accept, tok) in self.accepts.iter() {
+            if state_set.contains(accept) {
+                if let Some(output) = Output::select(accept_output.clone().flatten(), tok.clone())?
+                {
+                    accept_output = Some(output);
+                }
+            }
+        }
+        Ok(accept_output)
     }
 
     fn move_set(&self, state_set: &BTreeSet<StateRef>, b: u8) -> BTreeSet<StateRef> {
@@ -359,37 +833,100 @@ impl Nfa {
     }
 
     /// Subset construction
+    ///
+    /// If a state set contains look-around assertions, the kind of the previous byte is part of
+    /// the DFA state, so the assertions can be resolved once the next byte is known.
     pub fn into_dfa(self) -> Result<Dfa, Error> {
+        Ok(self
+            .into_dfa_within(usize::MAX)?
+            .expect("the number of states is unlimited"))
+    }
+
+    /// Subset construction, which stops as soon as the DFA has more than `max_states` states.
+    ///
+    /// Returns `None` if the limit is exceeded.
+    pub fn into_dfa_within(self, max_states: usize) -> Result<Option<Dfa>, Error> {
         let mut automaton = Dfa::new();
 
+        let nfa_has_looks = self.states.iter().any(|state| !state.looks.is_empty());
+        let kind_patterns = ByteKind::ALL
+            .iter()
+            .filter(|_| nfa_has_looks)
+            .filter_map(|kind| kind.pattern())
+            .collect::<Vec<_>>();
+        let byte_classes = ByteClasses::new(
+            self.states
+                .iter()
+                .flat_map(|state| state.transitions.iter().map(|t| &t.when))
+                .chain(kind_patterns.iter()),
+        );
+
         let mut start_set = BTreeSet::from_iter([self.start]);
         self.epsilon_closure(&mut start_set);
 
         let mut dstates = HashMap::new();
-        dstates.insert(start_set.clone(), self.start);
-        let mut todo = vec![start_set];
-        while let Some(state_set) = todo.pop() {
-            let dfa_state = *dstates.get(&state_set).unwrap();
-            for (accept, tok) in self.accepts.iter() {
-                if state_set.contains(accept) {
-                    automaton.set_accept_output(dfa_state, tok.clone())?;
+        let mut todo = vec![];
+        if self.has_looks(&start_set) {
+            // the boundary start state is the regular start state
+            for kind in ByteKind::ALL {
+                let dfa_state = if kind == ByteKind::Boundary {
+                    automaton.start
+                } else {
+                    automaton.add()
+                };
+                automaton.starts.push(dfa_state);
+                dstates.insert((start_set.clone(), Some(kind)), dfa_state);
+                todo.push((start_set.clone(), Some(kind)));
+            }
+            todo.reverse();
+        } else {
+            dstates.insert((start_set.clone(), None), self.start);
+            todo.push((start_set, None));
+        }
+        while let Some(key) = todo.pop() {
+            let dfa_state = *dstates.get(&key).unwrap();
+            let (state_set, prev) = key;
+            if prev.is_none() {
+                if let Some(output) = self.accept_output(&state_set)? {
+                    automaton.set_accept_output(dfa_state, output)?;
+                }
+            } else {
+                let mut outputs = vec![];
+                for next in ByteKind::ALL {
+                    outputs.push(self.accept_output(&self.resolve_looks(&state_set, prev, next))?);
+                }
+                if outputs.iter().all(|output| *output == outputs[0]) {
+                    if let Some(output) = outputs.swap_remove(0) {
+                        automaton.set_accept_output(dfa_state, output)?;
+                    }
+                } else {
+                    automaton.next_accepts.insert(
+                        dfa_state,
+                        outputs.into_iter().map(Option::flatten).collect(),
+                    );
                 }
             }
-            for b in u8::MIN..=u8::MAX {
-                let move_set = self.move_set(&state_set, b);
+            for (b, class) in byte_classes.iter() {
+                let kind = ByteKind::of(Some(b));
+                let move_set = self.move_set(&self.resolve_looks(&state_set, prev, kind), b);
                 if move_set.is_empty() {
                     continue;
                 }
-                if let Some(next_dfa_state) = dstates.get(&move_set) {
-                    automaton.add_transition(dfa_state, Pattern::from_byte(b), *next_dfa_state);
+                let next_prev = self.has_looks(&move_set).then_some(kind);
+                let next_key = (move_set, next_prev);
+                if let Some(next_dfa_state) = dstates.get(&next_key) {
+                    automaton.add_transition(dfa_state, class.clone(), *next_dfa_state);
                     continue;
                 }
+                if automaton.states.len() == max_states {
+                    return Ok(None);
+                }
                 let next_dfa_state = automaton.add();
-                dstates.insert(move_set.clone(), next_dfa_state);
-                todo.push(move_set);
-                automaton.add_transition(dfa_state, Pattern::from_byte(b), next_dfa_state);
+                dstates.insert(next_key.clone(), next_dfa_state);
+                todo.push(next_key);
+                automaton.add_transition(dfa_state, class.clone(), next_dfa_state);
             }
         }
-        Ok(automaton)
+        Ok(Some(automaton))
     }
 }
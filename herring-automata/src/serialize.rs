@@ -0,0 +1,176 @@
+use crate::{Automaton, Output, Pattern, State, StateRef, TokenEnd, Transition};
+use regex_syntax::hir::{ClassBytes, ClassBytesRange, Look};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Reads the whitespace-separated fields written by `Automaton::serialize`.
+struct Reader<'a> {
+    text: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn word(&mut self) -> Option<&'a str> {
+        let text = self.text.trim_start();
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        self.text = &text[end..];
+        (end > 0).then(|| &text[..end])
+    }
+    fn usize(&mut self) -> Option<usize> {
+        self.word()?.parse().ok()
+    }
+    /// Strings are prefixed by their length, as they may contain whitespace.
+    fn string(&mut self) -> Option<String> {
+        let text = self.text.trim_start();
+        let (len, rest) = text.split_once(':')?;
+        let len = len.parse().ok()?;
+        let string = rest.get(..len)?;
+        self.text = &rest[len..];
+        Some(string.to_string())
+    }
+    fn list<T>(&mut self, mut f: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let len = self.usize()?;
+        (0..len).map(|_| f(self)).collect()
+    }
+    fn state(&mut self) -> Option<StateRef> {
+        self.usize().map(StateRef)
+    }
+    fn output(&mut self) -> Option<Option<Output>> {
+        if self.word()? == "-" {
+            return Some(None);
+        }
+        let priority = self.usize()?;
+        let value = (self.string()?, self.usize()?);
+        let end = match self.word()? {
+            "m" => TokenEnd::MatchEnd,
+            "b" => TokenEnd::BeforeMatchEnd(self.usize()?),
+            "a" => TokenEnd::AfterMatchStart(self.usize()?),
+            _ => return None,
+        };
+        let name = match self.word()? {
+            "-" => None,
+            _ => Some(self.string()?),
+        };
+        Some(Some(Output::new(priority, value, end, name)))
+    }
+}
+
+fn write_string(text: &mut String, string: &str) {
+    write!(text, " {}:{string}", string.len()).unwrap();
+}
+
+fn write_output(text: &mut String, output: &Option<Output>) {
+    let Some(output) = output else {
+        text.push_str(" -");
+        return;
+    };
+    write!(text, " + {}", output.priority).unwrap();
+    write_string(text, &output.value.0);
+    write!(text, " {}", output.value.1).unwrap();
+    match output.end {
+        TokenEnd::MatchEnd => text.push_str(" m"),
+        TokenEnd::BeforeMatchEnd(len) => write!(text, " b {len}").unwrap(),
+        TokenEnd::AfterMatchStart(len) => write!(text, " a {len}").unwrap(),
+    }
+    match &output.name {
+        Some(name) => {
+            text.push_str(" +");
+            write_string(text, name);
+        }
+        None => text.push_str(" -"),
+    }
+}
+
+impl<const IS_DETERMINISTIC: bool> Automaton<IS_DETERMINISTIC> {
+    /// Writes the automaton to a text, from which `deserialize` restores it.
+    ///
+    /// Equal automata yield the same text, so it also identifies an automaton.
+    pub fn serialize(&self) -> String {
+        let mut text = format!("{} {}", self.start.0, self.states.len());
+        for state in &self.states {
+            write!(text, "\n{}", state.transitions.len()).unwrap();
+            for t in &state.transitions {
+                write!(text, " {} {}", t.to.0, t.when.ranges().len()).unwrap();
+                for r in t.when.ranges() {
+                    write!(text, " {} {}", r.start(), r.end()).unwrap();
+                }
+            }
+            write!(text, " {}", state.looks.len()).unwrap();
+            for (look, to) in &state.looks {
+                write!(text, " {} {}", look.as_repr(), to.0).unwrap();
+            }
+        }
+        write!(text, "\n{}", self.accepts.len()).unwrap();
+        for (state, output) in &self.accepts {
+            write!(text, " {}", state.0).unwrap();
+            write_output(&mut text, output);
+        }
+        write!(text, "\n{}", self.starts.len()).unwrap();
+        for start in &self.starts {
+            write!(text, " {}", start.0).unwrap();
+        }
+        write!(text, "\n{}", self.next_accepts.len()).unwrap();
+        for (state, outputs) in &self.next_accepts {
+            write!(text, " {} {}", state.0, outputs.len()).unwrap();
+            for output in outputs {
+                write_output(&mut text, output);
+            }
+        }
+        text
+    }
+
+    /// Restores an automaton written by `serialize`.
+    ///
+    /// Returns `None` if the text is malformed.
+    pub fn deserialize(text: &str) -> Option<Self> {
+        let mut reader = Reader { text };
+        let start = reader.state()?;
+        let states = reader.list(|reader| {
+            let transitions = reader.list(|reader| {
+                let to = reader.state()?;
+                let ranges = reader.list(|reader| {
+                    Some(ClassBytesRange::new(
+                        reader.word()?.parse().ok()?,
+                        reader.word()?.parse().ok()?,
+                    ))
+                })?;
+                Some(Transition::new(
+                    Pattern::from_class(ClassBytes::new(ranges)),
+                    to,
+                ))
+            })?;
+            let looks = reader.list(|reader| {
+                Some((
+                    Look::from_repr(reader.word()?.parse().ok()?)?,
+                    reader.state()?,
+                ))
+            })?;
+            Some(State { transitions, looks })
+        })?;
+        let accepts = reader
+            .list(|reader| Some((reader.state()?, reader.output()?)))?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let starts = reader.list(Reader::state)?;
+        let next_accepts = reader
+            .list(|reader| Some((reader.state()?, reader.list(Reader::output)?)))?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let refs = std::iter::once(start)
+            .chain(states.iter().flat_map(|state| {
+                (state.transitions.iter().map(|t| t.to)).chain(state.looks.iter().map(|l| l.1))
+            }))
+            .chain(accepts.keys().copied())
+            .chain(starts.iter().copied())
+            .chain(next_accepts.keys().copied());
+        if reader.word().is_some() || refs.into_iter().any(|state| state.0 >= states.len()) {
+            return None;
+        }
+        Some(Self {
+            start,
+            accepts,
+            states,
+            starts,
+            next_accepts,
+        })
+    }
+}
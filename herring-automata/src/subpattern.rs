@@ -0,0 +1,161 @@
+use crate::{Error, Location, Subpattern};
+use std::collections::BTreeMap;
+
+const MAX_DEPTH: usize = 128;
+
+/// Prefix of the names of the groups, which contain complemented subpatterns.
+const COMPLEMENT_GROUP: &str = "__herring_complement_";
+
+/// Whether the capture group contains a complemented subpattern.
+pub(crate) fn is_complement_group(name: &str) -> bool {
+    name.starts_with(COMPLEMENT_GROUP)
+}
+
+/// Regex, in which the references `(?&name)` are replaced by the subpatterns, and which maps
+/// positions back to the regex or the subpatterns.
+pub(crate) struct Expansion<'a> {
+    pub(crate) regex: String,
+    source: &'a str,
+    subpatterns: &'a BTreeMap<String, Subpattern>,
+    /// Copied segments by their start in the expanded regex, with the subpattern they were copied
+    /// from and their start in it.
+    segments: Vec<(usize, Option<&'a str>, usize)>,
+    /// Number of complemented subpatterns, which numbers their groups.
+    complements: usize,
+}
+
+impl<'a> Expansion<'a> {
+    pub(crate) fn new(
+        source: &'a str,
+        subpatterns: &'a BTreeMap<String, Subpattern>,
+    ) -> Result<Self, Error> {
+        let mut expansion = Self {
+            regex: String::new(),
+            source,
+            subpatterns,
+            segments: vec![],
+            complements: 0,
+        };
+        expansion.expand(source, None, 0)?;
+        Ok(expansion)
+    }
+
+    fn push(&mut self, text: &str, origin: Option<&'a str>, start: usize) {
+        if !text.is_empty() {
+            self.segments.push((self.regex.len(), origin, start));
+            self.regex.push_str(text);
+        }
+    }
+
+    fn expand(
+        &mut self,
+        pattern: &'a str,
+        origin: Option<&'a str>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        let mut last = 0;
+        while let Some(start) = pattern[last..].find("(?&").map(|i| last + i) {
+            self.push(&pattern[last..start], origin, last);
+            let Some(end) = pattern[start..].find(')').map(|i| start + i) else {
+                return Err(Self::error(
+                    "use of undefined subpattern".to_string(),
+                    origin,
+                    start..pattern.len(),
+                ));
+            };
+            let name = &pattern[start + 3..end];
+            let Some((name, subpattern)) = self.subpatterns.get_key_value(name) else {
+                return Err(Self::error(
+                    format!("use of undefined subpattern `{name}`"),
+                    origin,
+                    start..end + 1,
+                ));
+            };
+            if depth == MAX_DEPTH {
+                return Err(Self::error(
+                    format!("exceeded maximum subpattern replacement depth of {MAX_DEPTH}"),
+                    origin,
+                    start..end + 1,
+                ));
+            }
+            // the complement is built from the automaton of the group, as the regex syntax has no
+            // complement operator
+            if subpattern.complement {
+                let group = format!("(?P<{COMPLEMENT_GROUP}{}>", self.complements);
+                self.complements += 1;
+                self.push(&group, origin, start);
+            } else {
+                self.push("(", origin, start);
+            }
+            self.expand(&subpattern.regex, Some(name), depth + 1)?;
+            self.push(")", origin, end);
+            last = end + 1;
+        }
+        self.push(&pattern[last..], origin, last);
+        Ok(())
+    }
+
+    fn error(message: String, origin: Option<&str>, range: std::ops::Range<usize>) -> Error {
+        Error {
+            message,
+            conflict: None,
+            location: Some(Location {
+                subpattern: origin.map(str::to_string),
+                range,
+            }),
+        }
+    }
+
+    /// Maps a position in the expanded regex to the regex or subpattern it was copied from.
+    fn locate(&self, offset: usize) -> (Option<&'a str>, usize) {
+        let index = self
+            .segments
+            .partition_point(|(start, _, _)| *start <= offset)
+            .saturating_sub(1);
+        match self.segments.get(index) {
+            Some((start, origin, origin_start)) => (*origin, origin_start + (offset - start)),
+            None => (None, offset),
+        }
+    }
+
+    /// Converts an error of the regex parser to an error at the original position, which shows
+    /// the regex or subpattern containing it.
+    pub(crate) fn syntax_error(&self, err: regex_syntax::Error) -> Error {
+        let (message, span) = match &err {
+            regex_syntax::Error::Parse(err) => (err.kind().to_string(), *err.span()),
+            regex_syntax::Error::Translate(err) => (err.kind().to_string(), *err.span()),
+            _ => return err.into(),
+        };
+        let (origin, start) = self.locate(span.start.offset);
+        let pattern = origin.map_or(self.source, |name| &self.subpatterns[name].regex);
+        let next_char_end = pattern[start..]
+            .chars()
+            .next()
+            .map_or(start, |c| start + c.len_utf8());
+        let end = match self.locate(span.end.offset.saturating_sub(1)) {
+            (end_origin, end) if end_origin == origin && end >= start => end + 1,
+            _ => next_char_end,
+        }
+        .max(next_char_end);
+        let line_start = pattern[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = pattern[start..]
+            .find('\n')
+            .map_or(pattern.len(), |i| start + i);
+        let indent = pattern[line_start..start].chars().count();
+        let width = pattern[start..end.min(line_end)].chars().count().max(1);
+        let heading = match origin {
+            Some(name) => format!("regex parse error in subpattern `{name}`"),
+            None => "regex parse error".to_string(),
+        };
+        Self::error(
+            format!(
+                "{heading}:\n    {}\n    {}{}\nerror: {message}",
+                pattern[line_start..line_end].replace('\t', " "),
+                " ".repeat(indent),
+                "^".repeat(width),
+            ),
+            origin,
+            start..end,
+        )
+    }
+}
@@ -0,0 +1,54 @@
+use herring_automata::{CaptureInst, Captures, CaseFolding, Subpattern};
+use std::collections::BTreeMap;
+
+fn captures(regex: &str) -> Result<Option<Captures>, String> {
+    Captures::from_regex_with_subpatterns(regex, &BTreeMap::new(), CaseFolding::Disabled, false)
+        .map_err(|err| err.message)
+}
+
+#[test]
+fn without_groups() {
+    assert!(captures("a(b)c").unwrap().is_none());
+}
+
+#[test]
+fn counted_repetition() {
+    let captures = captures("(?<a>x){2,3}").unwrap().unwrap();
+    assert_eq!(captures.names(), ["a"]);
+    let ranges = captures
+        .insts()
+        .iter()
+        .filter(|inst| matches!(inst, CaptureInst::Ranges(..)))
+        .count();
+    assert_eq!(ranges, 3);
+}
+
+#[test]
+fn large_repetition() {
+    let err = captures("(?<a>[a-z]{100}){1000}").unwrap_err();
+    assert!(err.contains("instructions"), "{err}");
+}
+
+#[test]
+fn anchors() {
+    let err = captures(r"\b(?<a>x)").unwrap_err();
+    assert!(err.contains("anchors"), "{err}");
+    assert!(captures(r"\bx").unwrap().is_none());
+}
+
+#[test]
+fn complement() {
+    let subpatterns = BTreeMap::from([(
+        "body".to_string(),
+        Subpattern {
+            regex: "a".to_string(),
+            complement: true,
+        },
+    )]);
+    let result = |regex| {
+        Captures::from_regex_with_subpatterns(regex, &subpatterns, CaseFolding::Disabled, false)
+    };
+    let err = result("(?<a>x)(?&body)").unwrap_err();
+    assert!(err.message.contains("complemented"), "{}", err.message);
+    assert!(result("x(?&body)").unwrap().is_none());
+}
@@ -0,0 +1,146 @@
+use herring_automata::{CaseFolding, Dfa, Nfa, Token};
+
+fn nfa(regex: &str) -> Nfa {
+    Nfa::from_regex(regex, CaseFolding::Disabled, false)
+        .unwrap()
+        .0
+}
+
+/// Builds the DFA of a lexer, whose tokens are named by the index of their regex.
+fn lexer(regexes: &[&str]) -> Dfa {
+    let tokens = regexes
+        .iter()
+        .enumerate()
+        .map(|(i, regex)| {
+            let (nfa, priority) = Nfa::from_regex(regex, CaseFolding::Disabled, false).unwrap();
+            Token::new(nfa, priority, (i.to_string(), 0))
+        })
+        .collect();
+    Nfa::new_tokenizer(tokens).into_dfa().unwrap()
+}
+
+fn matches(dfa: &Dfa, word: &str) -> bool {
+    let mut state = dfa.start();
+    for b in word.bytes() {
+        let transitions = dfa.states()[state.value()].transitions();
+        match transitions.iter().find(|t| t.when().contains(b)) {
+            Some(t) => state = t.to(),
+            None => return false,
+        }
+    }
+    dfa.accepts().contains_key(&state)
+}
+
+#[test]
+fn shortest_unlexable() {
+    assert_eq!(
+        lexer(&["[a-z]+", " "]).shortest_unlexable(true),
+        Some(b"!".to_vec())
+    );
+    // every prefix of an unclosed string is rejected, while `"` starts no other token
+    let strings = lexer(&[r#"[^"]"#, r#""[^"]*""#]);
+    assert_eq!(strings.shortest_unlexable(true), Some(b"\"".to_vec()));
+    assert_eq!(strings.first_error(b"a\"b\"\"c"), Some(4));
+    assert_eq!(strings.first_error(b"a\"b\""), None);
+}
+
+#[test]
+fn shortest_unlexable_with_assertion() {
+    // `a` is only a token at the end of the input
+    let dfa = lexer(&["[^a]", r"a\z"]);
+    assert_eq!(dfa.shortest_unlexable(true), Some(b"a ".to_vec()));
+}
+
+#[test]
+fn total_lexer() {
+    let dfa = lexer(&["(?s:.)"]);
+    assert_eq!(dfa.shortest_unlexable(true), None);
+    // in binary lexers invalid UTF-8 must be lexed as well
+    let err = dfa.shortest_unlexable(false).unwrap();
+    assert!(std::str::from_utf8(&err).is_err(), "{err:?}");
+}
+
+#[test]
+fn shortest_common_word() {
+    assert_eq!(
+        nfa("[a-z]+").shortest_common_word(&nfa("[0-9b-c]{3}")),
+        Some(b"bbb".to_vec())
+    );
+    assert_eq!(
+        nfa("ab|abc|x*").shortest_common_word(&nfa("a.*")),
+        Some(b"ab".to_vec())
+    );
+    assert_eq!(nfa("[a-z]+").shortest_common_word(&nfa("[0-9]+")), None);
+}
+
+#[test]
+fn intersection() {
+    let dfa = lexer(&["[a-z]+[0-9]"]).intersection(&lexer(&["a.*"]));
+    assert!(matches(&dfa, "ab1"));
+    assert!(!matches(&dfa, "ba1"));
+    assert!(!matches(&dfa, "ab"));
+}
+
+#[test]
+fn complement() {
+    let dfa = lexer(&["ab*"]).complement();
+    assert!(matches(&dfa, ""));
+    assert!(matches(&dfa, "b"));
+    assert!(matches(&dfa, "aba"));
+    assert!(matches(&dfa, "\u{e9}"));
+    assert!(!matches(&dfa, "a"));
+    assert!(!matches(&dfa, "abbb"));
+}
+
+#[test]
+fn difference() {
+    let dfa = lexer(&["[a-z]+"]).difference(&lexer(&["if|else"]));
+    assert!(matches(&dfa, "i"));
+    assert!(matches(&dfa, "iff"));
+    assert!(matches(&dfa, "els"));
+    assert!(!matches(&dfa, "if"));
+    assert!(!matches(&dfa, "else"));
+    assert!(!matches(&dfa, "1"));
+}
+
+#[test]
+fn minimization() {
+    let dfa = lexer(&["(a|b)*abb"]);
+    let minimal = dfa.clone().into_minimized();
+    assert_eq!(minimal.states().len(), 4);
+    for word in ["abb", "babb", "aabb", "ab", "abba", "bbb", ""] {
+        assert_eq!(matches(&minimal, word), matches(&dfa, word), "{word}");
+    }
+}
+
+#[test]
+fn minimization_keeps_outputs() {
+    // the accept states of `a` and `b` are only merged, if they have the same output
+    assert_eq!(lexer(&["a", "b"]).into_minimized().states().len(), 3);
+    assert_eq!(lexer(&["a|b"]).into_minimized().states().len(), 2);
+}
+
+#[test]
+fn state_limit() {
+    let nfa = || nfa("[a-z]{10}|[0-9]");
+    assert!(nfa().into_dfa_within(10).unwrap().is_none());
+    let dfa = nfa().into_dfa_within(12).unwrap().unwrap();
+    assert_eq!(dfa.states().len(), 12);
+}
+
+#[test]
+fn serialization() {
+    let dfa = lexer(&["[a-z]+", r"\bif\b", "[ \t]+"]);
+    assert!(!dfa.starts().is_empty());
+    let text = dfa.serialize();
+    let restored = Dfa::deserialize(&text).unwrap();
+    assert_eq!(restored.serialize(), text);
+    assert_eq!(restored.accepts(), dfa.accepts());
+    assert_eq!(restored.next_accepts(), dfa.next_accepts());
+    for word in ["if", "iff", " \t", "1", ""] {
+        assert_eq!(matches(&restored, word), matches(&dfa, word), "{word}");
+    }
+    assert!(Dfa::deserialize(&format!("{text} 0")).is_none());
+    // the start state is out of bounds
+    assert!(Dfa::deserialize("1 1\n0 0\n0\n0\n0").is_none());
+}
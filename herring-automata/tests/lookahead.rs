@@ -0,0 +1,40 @@
+use herring_automata::{Location, Nfa};
+
+#[test]
+fn split_lookahead_at_end() {
+    assert_eq!(Nfa::split_lookahead("ab(?=c)").unwrap(), ("ab", Some("c")));
+    assert_eq!(
+        Nfa::split_lookahead("(a|b)(?=c|d)").unwrap(),
+        ("(a|b)", Some("c|d"))
+    );
+    assert_eq!(Nfa::split_lookahead("a|b").unwrap(), ("a|b", None));
+    assert_eq!(
+        Nfa::split_lookahead(r"a\(?=b\)").unwrap(),
+        (r"a\(?=b\)", None)
+    );
+    assert_eq!(
+        Nfa::split_lookahead("a[(?=b)]").unwrap(),
+        ("a[(?=b)]", None)
+    );
+}
+
+#[test]
+fn split_lookahead_after_alternation() {
+    let err = Nfa::split_lookahead("a|b(?=c)").unwrap_err();
+    assert!(err.message.contains("alternation"), "{}", err.message);
+    assert_eq!(
+        err.location,
+        Some(Location {
+            subpattern: None,
+            range: 3..8
+        })
+    );
+}
+
+#[test]
+fn split_lookahead_inside_group() {
+    for regex in ["(a(?=b))", "a(?=b)c", "a(?=b)*"] {
+        let err = Nfa::split_lookahead(regex).unwrap_err();
+        assert!(err.message.contains("end of a regex"), "{}", err.message);
+    }
+}
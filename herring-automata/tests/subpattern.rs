@@ -0,0 +1,61 @@
+use herring_automata::{CaseFolding, Location, Nfa, Subpattern};
+use std::collections::BTreeMap;
+
+fn location(regex: &str, subpatterns: &[(&str, &str)]) -> Location {
+    let subpatterns = subpatterns
+        .iter()
+        .map(|(name, regex)| {
+            let subpattern = Subpattern {
+                regex: regex.to_string(),
+                complement: false,
+            };
+            (name.to_string(), subpattern)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let err = Nfa::from_regex_with_subpatterns(regex, &subpatterns, CaseFolding::Disabled, false)
+        .unwrap_err();
+    err.location.unwrap()
+}
+
+fn at(subpattern: Option<&str>, range: std::ops::Range<usize>) -> Location {
+    Location {
+        subpattern: subpattern.map(str::to_string),
+        range,
+    }
+}
+
+#[test]
+fn error_in_regex_after_subpattern() {
+    assert_eq!(
+        location("(?&digits)[z-a]", &[("digits", "[0-9]+")]),
+        at(None, 11..14)
+    );
+}
+
+#[test]
+fn error_in_subpattern() {
+    assert_eq!(
+        location("x(?&digits)y", &[("digits", "[0-9]{2,1}")]),
+        at(Some("digits"), 5..10)
+    );
+}
+
+#[test]
+fn error_in_nested_subpattern() {
+    assert_eq!(
+        location(
+            "(?&number)",
+            &[("number", "-?(?&digits)"), ("digits", "[9-0]")]
+        ),
+        at(Some("digits"), 1..4)
+    );
+}
+
+#[test]
+fn undefined_subpattern() {
+    assert_eq!(location("a(?&nope)", &[]), at(None, 1..9));
+    assert_eq!(
+        location("a(?&outer)", &[("outer", "b(?&inner)")]),
+        at(Some("outer"), 1..10)
+    );
+}
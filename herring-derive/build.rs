@@ -0,0 +1,37 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+
+fn hash_dir(dir: &Path, hasher: &mut DefaultHasher) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            hash_dir(&path, hasher);
+        } else if let Ok(content) = std::fs::read(&path) {
+            path.file_name().hash(hasher);
+            content.hash(hasher);
+        }
+    }
+}
+
+/// The cached automata must be invalidated whenever the generator or the automata change, even if
+/// the version numbers stay the same, so their sources are part of the cache key.
+///
+/// The sources of the automata are only found in the workspace, as they are not part of the
+/// package of this crate, so a published generator relies on `herring_automata::VERSION`.
+fn main() {
+    let mut hasher = DefaultHasher::new();
+    for dir in ["src", "../herring-automata/src"] {
+        // cargo reruns the build script on every build for paths that do not exist
+        if Path::new(dir).is_dir() {
+            println!("cargo:rerun-if-changed={dir}");
+            hash_dir(Path::new(dir), &mut hasher);
+        }
+    }
+    println!("cargo:rustc-env=HERRING_BUILD_ID={:016x}", hasher.finish());
+}
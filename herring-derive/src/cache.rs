@@ -0,0 +1,314 @@
+use crate::debug::{DebugMode, DebugModes};
+use herring_automata::{Dfa, Nfa};
+use std::ffi::OsString;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Number of cached automata, above which the least recently used ones are removed.
+const MAX_ENTRIES: usize = 256;
+
+/// Versions of the generator and of the automata and the hash of their sources.
+const BUILD: [&str; 3] = [
+    env!("CARGO_PKG_VERSION"),
+    herring_automata::VERSION,
+    env!("HERRING_BUILD_ID"),
+];
+
+/// Location of the minimized DFA of a lexer in the on-disk cache.
+///
+/// Only the construction of the DFA is skipped by a cache hit, as the code is always generated
+/// from the automaton, so callbacks and types of the user keep their source locations. The cache
+/// is keyed by the tokenizer NFA and the exact build of the generator, so an entry can only be
+/// reused if constructing the DFA again would yield the same automaton.
+pub(crate) struct CacheEntry {
+    path: PathBuf,
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    // unit tests of the generator must neither depend on nor fill the cache of the workspace
+    if cfg!(test) {
+        return None;
+    }
+    select_cache_dir(
+        std::env::var("HERRING_CACHE").ok().as_deref(),
+        std::env::var_os("OUT_DIR"),
+        target_dir(),
+    )
+}
+
+/// Selects the cache directory by `HERRING_CACHE`, `OUT_DIR` and the target directory.
+fn select_cache_dir(
+    cache: Option<&str>,
+    out_dir: Option<OsString>,
+    target_dir: Option<PathBuf>,
+) -> Option<PathBuf> {
+    if let Some(cache) = cache {
+        return match cache {
+            "" | "0" | "off" | "false" => None,
+            dir => Some(PathBuf::from(dir)),
+        };
+    }
+    if let Some(dir) = out_dir {
+        return Some(Path::new(&dir).join("herring"));
+    }
+    Some(target_dir?.join("herring"))
+}
+
+/// Returns the target directory, which is needed by crates without a build script, as they have
+/// no `OUT_DIR`.
+pub(crate) fn target_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("CARGO_TARGET_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    // search for the target directory of the package or workspace, which is tagged by cargo
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").ok()?);
+    manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("target"))
+        .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+}
+
+impl CacheEntry {
+    pub(crate) fn new(
+        nfa: &Nfa,
+        max_states: usize,
+        values: &[&(String, usize)],
+        debug: &DebugModes,
+    ) -> Option<Self> {
+        Self::in_dir(cache_dir()?, nfa, max_states, values, debug)
+    }
+
+    fn in_dir(
+        dir: PathBuf,
+        nfa: &Nfa,
+        max_states: usize,
+        values: &[&(String, usize)],
+        debug: &DebugModes,
+    ) -> Option<Self> {
+        // the intermediate automata and the duration of their construction are only known, if the
+        // DFA is constructed again
+        let modes = [DebugMode::Graphviz, DebugMode::Mermaid, DebugMode::Stats];
+        if modes.into_iter().any(|mode| debug.enabled(mode)) {
+            return None;
+        }
+        let key = key(&BUILD, nfa, max_states, values);
+        Some(Self {
+            path: dir.join(format!("{key:016x}.dfa")),
+        })
+    }
+
+    pub(crate) fn load(&self) -> Option<Dfa> {
+        let dfa = Dfa::deserialize(&std::fs::read_to_string(&self.path).ok()?)?;
+        // the modification time marks the last use for evicting old entries
+        if let Ok(file) = std::fs::File::options().append(true).open(&self.path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(dfa)
+    }
+
+    /// Errors are ignored, as a missing cache entry only costs build time.
+    pub(crate) fn store(&self, dfa: &Dfa) {
+        let Some(dir) = self.path.parent() else {
+            return;
+        };
+        if std::fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        // other rustc processes may read the entry concurrently, so it has to be written atomically
+        let tmp_path = self
+            .path
+            .with_extension(format!("dfa.{}.tmp", std::process::id()));
+        if std::fs::write(&tmp_path, dfa.serialize())
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .is_err()
+        {
+            let _ = std::fs::remove_file(&tmp_path);
+            return;
+        }
+        evict(dir);
+    }
+}
+
+/// Hashes the build of the generator and the inputs of the DFA construction.
+///
+/// The values of the tokens replace the values of their attributes in the DFA before it is
+/// minimized.
+fn key(build: &[&str], nfa: &Nfa, max_states: usize, values: &[&(String, usize)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    build.hash(&mut hasher);
+    nfa.serialize().hash(&mut hasher);
+    max_states.hash(&mut hasher);
+    values.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Removes the least recently used entries, if the cache has more than `MAX_ENTRIES` entries.
+fn evict(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            if path.extension()? != "dfa" {
+                return None;
+            }
+            Some((entry.metadata().ok()?.modified().ok()?, path))
+        })
+        .collect::<Vec<_>>();
+    if entries.len() <= MAX_ENTRIES {
+        return;
+    }
+    entries.sort();
+    for (_, path) in &entries[..entries.len() - MAX_ENTRIES] {
+        // another process may have removed the entry already
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evict, key, select_cache_dir, CacheEntry, BUILD, MAX_ENTRIES};
+    use crate::debug::{DebugMode, DebugModes};
+    use crate::parse::DebugAttrs;
+    use herring_automata::{CaseFolding, Nfa, Token};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    fn nfa(regexes: &[&str]) -> Nfa {
+        Nfa::new_tokenizer(
+            regexes
+                .iter()
+                .enumerate()
+                .map(|(i, regex)| {
+                    let (nfa, priority) =
+                        Nfa::from_regex(regex, CaseFolding::Disabled, false).unwrap();
+                    Token::new(nfa, priority, (format!("T{i}"), i))
+                })
+                .collect(),
+        )
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("herring-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn debug_modes(modes: &[DebugMode]) -> DebugModes {
+        DebugModes::new(&DebugAttrs {
+            modes: modes.iter().copied().collect(),
+            dir: None,
+        })
+    }
+
+    #[test]
+    fn key_stability() {
+        let values = [&("T0".to_string(), 0), &("T1".to_string(), 1)];
+        let lexer = || nfa(&["[a-z]+", "[0-9]+"]);
+        let base = key(&BUILD, &lexer(), usize::MAX, &values);
+        assert_eq!(key(&BUILD, &lexer(), usize::MAX, &values), base);
+        assert_ne!(
+            key(&BUILD, &nfa(&["[a-z]+", "[0-8]+"]), usize::MAX, &values),
+            base
+        );
+        assert_ne!(key(&BUILD, &lexer(), 100, &values), base);
+        assert_ne!(
+            key(&BUILD, &lexer(), usize::MAX, &[values[1], values[0]]),
+            base
+        );
+    }
+
+    #[test]
+    fn key_invalidation() {
+        let lexer = nfa(&["[a-z]+"]);
+        let values = [&("T0".to_string(), 0)];
+        let base = key(&["0.1.2", "0.1.3", "0123"], &lexer, usize::MAX, &values);
+        for build in [
+            ["0.1.3", "0.1.3", "0123"],
+            ["0.1.2", "0.1.4", "0123"],
+            ["0.1.2", "0.1.3", "0124"],
+        ] {
+            assert_ne!(key(&build, &lexer, usize::MAX, &values), base, "{build:?}");
+        }
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = temp_dir("cache-store");
+        let lexer = nfa(&["[a-z]+", "[0-9]+"]);
+        let values = [&("T0".to_string(), 0), &("T1".to_string(), 1)];
+        let entry = CacheEntry::in_dir(dir.clone(), &lexer, usize::MAX, &values, &debug_modes(&[]))
+            .unwrap();
+        assert!(entry.load().is_none());
+        let dfa = lexer.into_dfa().unwrap().into_minimized();
+        entry.store(&dfa);
+        assert_eq!(entry.load().unwrap().serialize(), dfa.serialize());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn eviction() {
+        let dir = temp_dir("cache-evict");
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        for i in 0..MAX_ENTRIES + 2 {
+            let path = dir.join(format!("{i:016x}.dfa"));
+            std::fs::write(&path, "").unwrap();
+            let file = std::fs::File::options().append(true).open(&path).unwrap();
+            file.set_modified(now - Duration::from_secs((MAX_ENTRIES + 2 - i) as u64))
+                .unwrap();
+        }
+        std::fs::write(dir.join("other.txt"), "").unwrap();
+        evict(&dir);
+        let exists = |name: &str| dir.join(name).exists();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), MAX_ENTRIES + 1);
+        // the least recently used entries are removed
+        assert!(!exists(&format!("{:016x}.dfa", 0)));
+        assert!(!exists(&format!("{:016x}.dfa", 1)));
+        assert!(exists(&format!("{:016x}.dfa", 2)));
+        assert!(exists("other.txt"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bypass() {
+        let out_dir = || Some("out".into());
+        let target_dir = || Some(PathBuf::from("target"));
+        for off in ["", "0", "off", "false"] {
+            assert_eq!(select_cache_dir(Some(off), out_dir(), target_dir()), None);
+        }
+        assert_eq!(
+            select_cache_dir(Some("cache"), out_dir(), target_dir()),
+            Some(PathBuf::from("cache"))
+        );
+        assert_eq!(
+            select_cache_dir(None, out_dir(), target_dir()),
+            Some(Path::new("out").join("herring"))
+        );
+        assert_eq!(
+            select_cache_dir(None, None, target_dir()),
+            Some(Path::new("target").join("herring"))
+        );
+        assert_eq!(select_cache_dir(None, None, None), None);
+
+        // the code is generated again from a cached DFA, but not the intermediate automata
+        let lexer = nfa(&["[a-z]+"]);
+        let values = [&("T0".to_string(), 0)];
+        let entry = |modes: &[DebugMode]| {
+            CacheEntry::in_dir(
+                PathBuf::from("cache"),
+                &lexer,
+                usize::MAX,
+                &values,
+                &debug_modes(modes),
+            )
+        };
+        assert!(entry(&[DebugMode::Expand, DebugMode::Overlaps]).is_some());
+        for mode in [DebugMode::Graphviz, DebugMode::Mermaid, DebugMode::Stats] {
+            assert!(entry(&[mode]).is_none(), "{mode:?}");
+        }
+    }
+}
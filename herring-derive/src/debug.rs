@@ -1,29 +1,163 @@
+use crate::diagnostics::format_word;
+use crate::parse::{DebugAttrs, TokenAttr};
 use herring_automata::Automaton;
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::quote;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-pub(crate) fn graph<const D: bool>(automaton: &Automaton<D>, name: &str) -> syn::Result<()> {
-    if let Ok(val) = std::env::var("HERRING_DEBUG") {
-        if val == "graphviz" {
-            graphviz(automaton, &format!("{name}.dot"))
-        } else if val == "mermaid" {
-            mermaid(automaton, &format!("{name}.mmd"))
-        } else {
-            Ok(())
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum DebugMode {
+    Expand,
+    Skip,
+    Graphviz,
+    Mermaid,
+    Stats,
+    Overlaps,
+}
+
+impl DebugMode {
+    const ALL: [DebugMode; 6] = [
+        DebugMode::Expand,
+        DebugMode::Skip,
+        DebugMode::Graphviz,
+        DebugMode::Mermaid,
+        DebugMode::Stats,
+        DebugMode::Overlaps,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            DebugMode::Expand => "expand",
+            DebugMode::Skip => "skip",
+            DebugMode::Graphviz => "graphviz",
+            DebugMode::Mermaid => "mermaid",
+            DebugMode::Stats => "stats",
+            DebugMode::Overlaps => "overlaps",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// Lists the names of all modes for error messages.
+    pub(crate) fn names() -> String {
+        Self::ALL
+            .map(|mode| format!("`{}`", mode.name()))
+            .join(", ")
+    }
+}
+
+/// Debug modes enabled for an enum by `#[herring(debug(...))]` and for all enums by the
+/// comma-separated modes in `HERRING_DEBUG`, with the directory for the debug files.
+pub(crate) struct DebugModes {
+    modes: BTreeSet<DebugMode>,
+    dir: PathBuf,
+}
+
+impl DebugModes {
+    pub(crate) fn new(attrs: &DebugAttrs) -> Self {
+        let mut modes = attrs.modes.clone();
+        if let Ok(val) = std::env::var("HERRING_DEBUG") {
+            let (env_modes, warnings) = parse_modes(&val);
+            modes.extend(env_modes);
+            for warning in warnings {
+                eprintln!("warning: {warning}");
+            }
+        }
+        let dir = debug_dir(
+            attrs.dir.as_deref(),
+            std::env::var_os("CARGO_MANIFEST_DIR"),
+            std::env::var_os("OUT_DIR"),
+            crate::cache::target_dir(),
+        );
+        Self { modes, dir }
+    }
+
+    pub(crate) fn enabled(&self, mode: DebugMode) -> bool {
+        self.modes.contains(&mode)
+    }
+
+    /// Path of a debug file, whose directory is created if necessary.
+    fn file(&self, name: &str) -> syn::Result<String> {
+        if let Err(err) = std::fs::create_dir_all(&self.dir) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("error creating debug directory: {err}"),
+            ));
+        }
+        Ok(self.dir.join(name).to_string_lossy().into_owned())
+    }
+
+    pub(crate) fn graph<const D: bool>(
+        &self,
+        automaton: &Automaton<D>,
+        name: &str,
+    ) -> syn::Result<()> {
+        if self.enabled(DebugMode::Graphviz) {
+            graphviz(automaton, &self.file(&format!("{name}.dot"))?)?;
+        }
+        if self.enabled(DebugMode::Mermaid) {
+            mermaid(automaton, &self.file(&format!("{name}.mmd"))?)?;
         }
-    } else {
         Ok(())
     }
 }
 
-fn graphviz<const D: bool>(
-    automaton: &herring_automata::Automaton<D>,
-    name: &str,
-) -> syn::Result<()> {
-    use proc_macro2::Span;
-    use syn::Error;
+/// Parses the comma-separated modes of `HERRING_DEBUG` and warns about unknown modes.
+fn parse_modes(val: &str) -> (BTreeSet<DebugMode>, Vec<String>) {
+    let mut modes = BTreeSet::new();
+    let mut warnings = vec![];
+    for name in val
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match DebugMode::from_name(name) {
+            Some(mode) => {
+                modes.insert(mode);
+            }
+            None if name == "log" => warnings.push(
+                "the HERRING_DEBUG mode `log` was removed, use `#[herring(trace)]` or the `trace` \
+                 feature instead"
+                    .to_string(),
+            ),
+            None => warnings.push(format!(
+                "unknown HERRING_DEBUG mode `{name}`, expected one of {}",
+                DebugMode::names()
+            )),
+        }
+    }
+    (modes, warnings)
+}
+
+/// Selects the directory for the debug files.
+///
+/// A configured directory is relative to the crate, otherwise the files are written to `OUT_DIR`
+/// of crates with a build script or to the target directory.
+fn debug_dir(
+    dir: Option<&str>,
+    manifest_dir: Option<OsString>,
+    out_dir: Option<OsString>,
+    target_dir: Option<PathBuf>,
+) -> PathBuf {
+    match (dir, out_dir) {
+        (Some(dir), _) => manifest_dir
+            .map_or_else(PathBuf::new, PathBuf::from)
+            .join(dir),
+        (None, Some(out_dir)) => PathBuf::from(out_dir).join("herring"),
+        (None, None) => target_dir.map_or_else(PathBuf::new, |dir| dir.join("herring-debug")),
+    }
+}
+
+fn graphviz<const D: bool>(automaton: &Automaton<D>, name: &str) -> syn::Result<()> {
     if let Err(err) = automaton.print_graphviz(name) {
-        return Err(Error::new(
+        return Err(syn::Error::new(
             Span::call_site(),
             format!("error writing debug file: {err}"),
         ));
@@ -31,14 +165,9 @@ fn graphviz<const D: bool>(
     Ok(())
 }
 
-fn mermaid<const D: bool>(
-    automaton: &herring_automata::Automaton<D>,
-    name: &str,
-) -> syn::Result<()> {
-    use proc_macro2::Span;
-    use syn::Error;
+fn mermaid<const D: bool>(automaton: &Automaton<D>, name: &str) -> syn::Result<()> {
     if let Err(err) = automaton.print_mermaid(name) {
-        return Err(Error::new(
+        return Err(syn::Error::new(
             Span::call_site(),
             format!("error writing debug file: {err}"),
         ));
@@ -46,38 +175,250 @@ fn mermaid<const D: bool>(
     Ok(())
 }
 
-pub(crate) fn log_state(state: usize) -> TokenStream {
-    if let Ok(val) = std::env::var("HERRING_DEBUG") {
-        if val == "log" {
-            return quote! { eprintln!("STATE: S{}", #state); };
+/// Writes all pairs of tokens, which match a common word, to `EnumName_overlaps.txt`.
+///
+/// The languages of the tokens are intersected before they are merged into the tokenizer, so
+/// overlaps resolved by the priorities are listed as well.
+pub(crate) fn overlaps(tokens: &[TokenAttr], debug: &DebugModes, name: &str) -> syn::Result<()> {
+    if !debug.enabled(DebugMode::Overlaps) {
+        return Ok(());
+    }
+    let mut report = String::new();
+    for (i, a) in tokens.iter().enumerate() {
+        for b in &tokens[i + 1..] {
+            if a.token.value() == b.token.value() {
+                continue;
+            }
+            let Some(word) = a.token.nfa().shortest_common_word(b.token.nfa()) else {
+                continue;
+            };
+            let winner = match a.token.priority().cmp(&b.token.priority()) {
+                Ordering::Greater => format!("`{}` wins", a.token.name()),
+                Ordering::Less => format!("`{}` wins", b.token.name()),
+                Ordering::Equal => "conflict".to_string(),
+            };
+            writeln!(
+                report,
+                "`{}` {} (priority {}) and `{}` {} (priority {}) match {}: {winner}",
+                a.token.name(),
+                a.source,
+                a.token.priority(),
+                b.token.name(),
+                b.source,
+                b.token.priority(),
+                format_word(&word),
+            )
+            .unwrap();
         }
     }
-    quote! {}
+    if let Err(err) = std::fs::write(debug.file(&format!("{name}_overlaps.txt"))?, report) {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            format!("error writing debug file: {err}"),
+        ));
+    }
+    Ok(())
 }
 
-pub(crate) fn expand_or_skip(output: TokenStream, enum_name: &Ident) -> syn::Result<TokenStream> {
-    fn write_file(output: TokenStream, enum_name: &Ident) -> std::io::Result<()> {
-        use std::io::Write;
-        let mut f =
-            std::fs::File::create(format!("{}_lexer.rs", enum_name.to_string().to_lowercase()))?;
-        write!(f, "{output}")
-    }
-    if let Ok(val) = std::env::var("HERRING_DEBUG") {
-        match val.as_str() {
-            "expand" => {
-                if let Err(err) = write_file(output, enum_name) {
-                    return Err(syn::Error::new(
-                        Span::call_site(),
-                        format!("error writing output file: {err}"),
-                    ));
-                }
-                return Ok(quote! {});
-            }
-            "skip" => {
-                return Ok(quote! {});
-            }
-            _ => {}
+/// Sizes of the automata and of the generated code and the time spent in each phase, which are
+/// printed to stderr by `HERRING_DEBUG=stats`.
+pub(crate) struct Stats {
+    phase_start: Instant,
+    phases: Vec<(&'static str, Duration)>,
+    counts: Vec<(&'static str, usize)>,
+}
+
+impl Stats {
+    pub(crate) fn new() -> Self {
+        Self {
+            phase_start: Instant::now(),
+            phases: vec![],
+            counts: vec![],
+        }
+    }
+
+    /// Records the time since the end of the previous phase.
+    pub(crate) fn phase(&mut self, name: &'static str) {
+        let now = Instant::now();
+        self.phases.push((name, now - self.phase_start));
+        self.phase_start = now;
+    }
+
+    pub(crate) fn count(&mut self, name: &'static str, count: usize) {
+        self.counts.push((name, count));
+    }
+
+    pub(crate) fn print(&self, enum_name: &Ident, debug: &DebugModes) {
+        if debug.enabled(DebugMode::Stats) {
+            eprint!("{}", self.report(enum_name));
+        }
+    }
+
+    fn report(&self, enum_name: &Ident) -> String {
+        let mut report = format!("herring stats for `{enum_name}`:\n");
+        for (name, count) in &self.counts {
+            let _ = writeln!(report, "  {name}: {count}");
+        }
+        for (name, duration) in &self.phases {
+            let _ = writeln!(
+                report,
+                "  {name}: {:.3} ms",
+                duration.as_secs_f64() * 1000.0
+            );
         }
+        report
+    }
+}
+
+/// Writes the generated code to `enum_name_lexer.rs` for `expand` and omits it for `skip`, so
+/// `expand` alone still compiles the lexer.
+pub(crate) fn expand_or_skip(
+    output: TokenStream,
+    enum_name: &Ident,
+    debug: &DebugModes,
+) -> syn::Result<TokenStream> {
+    if debug.enabled(DebugMode::Expand) {
+        let file = debug.file(&format!(
+            "{}_lexer.rs",
+            enum_name.to_string().to_lowercase()
+        ))?;
+        if let Err(err) = std::fs::write(file, output.to_string()) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("error writing output file: {err}"),
+            ));
+        }
+    }
+    if debug.enabled(DebugMode::Skip) {
+        return Ok(quote! {});
     }
     Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{debug_dir, parse_modes, DebugMode, Stats};
+    use crate::generate::{generate_impl, generate_with_stats};
+    use proc_macro2::{Ident, Span};
+    use quote::quote;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn stats() {
+        let mut stats = Stats::new();
+        generate_with_stats(
+            quote! {
+                enum Token {
+                    #[regex("[a-z]+")]
+                    Ident,
+                    #[regex("[0-9A-F]+")]
+                    Hex,
+                    #[regex("[ -/:-@]")]
+                    Punct,
+                }
+            },
+            &mut stats,
+        )
+        .unwrap();
+        let report = stats.report(&Ident::new("Token", Span::call_site()));
+        let lines = report.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..7],
+            [
+                "herring stats for `Token`:",
+                "  NFA states: 7",
+                "  subset DFA states: 4",
+                "  minimized DFA states: 4",
+                "  jump table states: 1",
+                "  LUT patterns: 1",
+                "  lookup tables: 1",
+            ]
+        );
+        let phases = lines[7..]
+            .iter()
+            .map(|line| line.split(':').next().unwrap().trim())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            phases,
+            [
+                "parsing",
+                "NFA construction",
+                "subset construction",
+                "minimization",
+                "code generation"
+            ]
+        );
+        assert!(lines[7..].iter().all(|line| line.ends_with(" ms")));
+    }
+
+    #[test]
+    fn overlaps() {
+        let dir = std::env::temp_dir().join(format!("herring-overlaps-{}", std::process::id()));
+        let dir_lit = dir.to_str().unwrap();
+        // the report is written before the conflict is found in the automaton
+        assert!(generate_impl(quote! {
+            #[herring(debug(overlaps, dir = #dir_lit))]
+            enum Token {
+                #[token("if")]
+                If,
+                #[regex("[a-z]+")]
+                Ident,
+                #[regex("[0-9]+")]
+                #[regex("[0-9]+[.]")]
+                Number,
+                #[regex("[0-9][0-9_]*")]
+                Digits,
+            }
+        })
+        .is_err());
+        let report = std::fs::read_to_string(dir.join("Token_overlaps.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            [
+                r#"`If` token("if") (priority 4) and `Ident` regex("[a-z]+") (priority 2) match "if": `If` wins"#,
+                r#"`Number` regex("[0-9]+") (priority 2) and `Digits` regex("[0-9][0-9_]*") (priority 2) match "0": conflict"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let (modes, warnings) = parse_modes(" stats,, graphviz ,stats");
+        assert_eq!(
+            modes.into_iter().collect::<Vec<_>>(),
+            [DebugMode::Graphviz, DebugMode::Stats]
+        );
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let (modes, warnings) = parse_modes("log,expand,graph");
+        assert_eq!(modes.into_iter().collect::<Vec<_>>(), [DebugMode::Expand]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("the HERRING_DEBUG mode `log` was removed"));
+        assert!(warnings[1].starts_with("unknown HERRING_DEBUG mode `graph`, expected one of"));
+    }
+
+    #[test]
+    fn dir() {
+        let manifest_dir = || Some("crate".into());
+        let out_dir = || Some("out".into());
+        let target_dir = || Some(PathBuf::from("target"));
+        assert_eq!(
+            debug_dir(Some("debug"), manifest_dir(), out_dir(), target_dir()),
+            Path::new("crate").join("debug")
+        );
+        assert_eq!(
+            debug_dir(None, manifest_dir(), out_dir(), target_dir()),
+            Path::new("out").join("herring")
+        );
+        assert_eq!(
+            debug_dir(None, manifest_dir(), None, target_dir()),
+            Path::new("target").join("herring-debug")
+        );
+        // without any known directory the files are written to the working directory
+        assert_eq!(debug_dir(None, None, None, None), PathBuf::new());
+        assert_eq!(
+            debug_dir(Some("debug"), None, None, None),
+            Path::new("debug")
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use crate::parse::TokenAttr;
+use herring_automata::{Dfa, Output, Subpattern};
+use proc_macro2::Span;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+use syn::{Error, LitStr};
+
+/// Errors collected while parsing the attributes, so they are reported together.
+#[derive(Default)]
+pub(crate) struct Errors(Option<Error>);
+
+impl Errors {
+    pub(crate) fn push(&mut self, error: Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+    /// Reports the collected errors together with the error of the result.
+    pub(crate) fn finish<T>(mut self, result: syn::Result<T>) -> syn::Result<T> {
+        match result {
+            Ok(value) => match self.0 {
+                Some(errors) => Err(errors),
+                None => Ok(value),
+            },
+            Err(error) => {
+                self.push(error);
+                Err(self.0.unwrap())
+            }
+        }
+    }
+}
+
+/// Formats a word matched by a token as a string or byte string literal.
+pub(crate) fn format_word(word: &[u8]) -> String {
+    match std::str::from_utf8(word) {
+        Ok(word) => format!("\"{}\"", word.escape_debug()),
+        Err(_) => format!("b\"{}\"", word.escape_ascii()),
+    }
+}
+
+/// Reports tokens with the same priority, which match the same word, at both attributes.
+///
+/// The outputs are numbered by the index of their attribute, and the witness is a shortest word
+/// matched by both attributes.
+pub(crate) fn conflict_error(
+    conflict: &(Output, Output),
+    tokens: &[TokenAttr],
+    subpatterns: &BTreeMap<String, Subpattern>,
+) -> Option<Error> {
+    let (first, second) = conflict;
+    let (a, b) = (&tokens[first.value().1], &tokens[second.value().1]);
+    let word = a.token.nfa().shortest_common_word(b.token.nfa())?;
+    let mut error = Error::new(
+        a.span,
+        format!(
+            "tokens `{}` and `{}` both have priority {} and match the same word {}\n\
+             priority of `{}`: {}\n\
+             priority of `{}`: {}",
+            first.name(),
+            second.name(),
+            first.priority(),
+            format_word(&word),
+            first.name(),
+            a.explain_priority(subpatterns),
+            second.name(),
+            b.explain_priority(subpatterns),
+        ),
+    );
+    error.combine(Error::new(
+        b.span,
+        format!(
+            "`{}` also matches {} with priority {}",
+            second.name(),
+            format_word(&word),
+            second.priority(),
+        ),
+    ));
+    Some(error)
+}
+
+/// Reports the attributes, whose outputs are carried by no accept state of the DFA, in which the
+/// outputs are numbered by the index of their attribute.
+///
+/// Every word matched by such a token is matched by a token with a higher priority, so the
+/// witness is a shortest word of the token, which is matched by a token with a higher priority.
+pub(crate) fn shadowed_error(dfa: &Dfa, tokens: &[TokenAttr]) -> Option<Error> {
+    let reachable: HashSet<usize> = dfa
+        .accepts()
+        .values()
+        .chain(dfa.next_accepts().values().flatten())
+        .flatten()
+        .map(|output| output.value().1)
+        .collect();
+    let mut errors = tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !reachable.contains(i))
+        .map(|(_, attr)| shadowed_attr_error(attr, tokens));
+    let mut error = errors.next()?;
+    for other in errors {
+        error.combine(other);
+    }
+    Some(error)
+}
+
+fn shadowed_attr_error(attr: &TokenAttr, tokens: &[TokenAttr]) -> Error {
+    let name = attr.token.name();
+    let mut witness: Option<(&TokenAttr, Vec<u8>)> = None;
+    for other in tokens {
+        if other.token.priority() <= attr.token.priority() {
+            continue;
+        }
+        if let Some(word) = attr.token.nfa().shortest_common_word(other.token.nfa()) {
+            if witness.as_ref().is_none_or(|(_, w)| word.len() < w.len()) {
+                witness = Some((other, word));
+            }
+        }
+    }
+    let Some((other, word)) = witness else {
+        return Error::new(
+            attr.span,
+            format!("token `{name}` is unreachable, because tokens with a higher priority match all its words"),
+        );
+    };
+    let mut error = Error::new(
+        attr.span,
+        format!(
+            "token `{name}` is unreachable, because tokens with a higher priority match all its words\n\
+             e.g. {} is matched by `{}` with priority {} instead of priority {}",
+            format_word(&word),
+            other.token.name(),
+            other.token.priority(),
+            attr.token.priority(),
+        ),
+    );
+    error.combine(Error::new(
+        other.span,
+        format!("`{}` shadows `{name}`", other.token.name()),
+    ));
+    error
+}
+
+/// Reports a DFA exceeding the state budget during the subset construction together with the
+/// tokens, whose own DFAs have the most states.
+///
+/// The DFAs of the tokens are built with the same budget, so tokens exceeding it on their own are
+/// reported without completing their subset construction.
+pub(crate) fn state_budget_error(max_states: usize, span: Span, tokens: &[TokenAttr]) -> Error {
+    let mut sizes = tokens
+        .iter()
+        .filter_map(|attr| {
+            let dfa = attr.token.nfa().clone().into_dfa_within(max_states).ok()?;
+            let count = dfa.map_or(usize::MAX, |dfa| dfa.states().len());
+            let size = match count {
+                usize::MAX => format!("more than {max_states}"),
+                count => count.to_string(),
+            };
+            Some((attr, count, size))
+        })
+        .collect::<Vec<_>>();
+    sizes.sort_by_key(|(_, count, _)| std::cmp::Reverse(*count));
+    sizes.truncate(3);
+    let largest = sizes
+        .iter()
+        .map(|(attr, _, size)| format!("`{}` ({size} states)", attr.token.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut error = Error::new(
+        span,
+        format!(
+            "the DFA exceeds the limit of {max_states} states during the subset construction\n\
+             tokens with the most states on their own: {largest}"
+        ),
+    );
+    for (attr, _, size) in sizes {
+        error.combine(Error::new(
+            attr.span,
+            format!("`{}` needs {size} states on its own", attr.token.name()),
+        ));
+    }
+    error
+}
+
+/// Maps a byte offset in the value of a string literal to the offset in its source code.
+fn source_offset(source: &str, offset: usize) -> Option<usize> {
+    if let Some(raw) = source.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return Some(offset + hashes + 2);
+    }
+    let mut chars = source.char_indices().skip(1).peekable();
+    let mut value_offset = 0;
+    while let Some((i, c)) = chars.next() {
+        // line continuations are skipped before the offset is checked, so a range starting after
+        // one does not include it
+        if c == '\\' && chars.next_if(|(_, c)| *c == '\n' || *c == '\r').is_some() {
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            continue;
+        }
+        if value_offset >= offset {
+            return Some(i);
+        }
+        match c {
+            '"' => return None,
+            '\\' => match chars.next()?.1 {
+                'x' => {
+                    chars.nth(1)?;
+                    value_offset += 1;
+                }
+                'u' => {
+                    let mut code = String::new();
+                    for (_, c) in chars.by_ref().skip(1) {
+                        if c == '}' {
+                            break;
+                        }
+                        code.push(c);
+                    }
+                    value_offset +=
+                        char::from_u32(u32::from_str_radix(&code, 16).ok()?)?.len_utf8();
+                }
+                _ => value_offset += 1,
+            },
+            c => value_offset += c.len_utf8(),
+        }
+    }
+    None
+}
+
+/// Returns the span of the byte range in the value of the literal, or the span of the literal
+/// itself, if the compiler cannot create spans inside of literals.
+fn lit_subspan(lit: &LitStr, range: &Range<usize>) -> Span {
+    let token = lit.token();
+    let source = token.to_string();
+    source_offset(&source, range.start)
+        .zip(source_offset(&source, range.end))
+        .and_then(|(start, end)| token.subspan(start..end))
+        .unwrap_or_else(|| lit.span())
+}
+
+/// Reports an error of a regex at its location in the regex literal or, if it is located in a
+/// subpattern, in the literal of the subpattern.
+pub(crate) fn regex_error(
+    err: herring_automata::Error,
+    span: Span,
+    lit: Option<&LitStr>,
+    subpattern_lits: &BTreeMap<String, LitStr>,
+) -> Error {
+    let Some(location) = &err.location else {
+        return Error::new(span, err.message);
+    };
+    match &location.subpattern {
+        None => Error::new(
+            lit.map_or(span, |lit| lit_subspan(lit, &location.range)),
+            err.message,
+        ),
+        Some(name) => match subpattern_lits.get(name) {
+            Some(subpattern_lit) => {
+                let mut error =
+                    Error::new(lit_subspan(subpattern_lit, &location.range), err.message);
+                error.combine(Error::new(
+                    span,
+                    format!("subpattern `{name}` is used by this regex"),
+                ));
+                error
+            }
+            None => Error::new(span, err.message),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::source_offset;
+    use crate::generate::generate_impl;
+    use quote::quote;
+    use syn::LitStr;
+
+    fn error(tokens: proc_macro2::TokenStream) -> String {
+        match generate_impl(tokens) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.into_iter().map(|err| err.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn shadowed_attribute_of_variant() {
+        let message = error(quote! {
+            enum Token {
+                #[token("if")]
+                If,
+                #[regex("[a-z]+")]
+                #[regex("i[f]", priority = 1)]
+                Ident,
+            }
+        });
+        assert!(
+            message.starts_with("token `Ident` is unreachable"),
+            "{message}"
+        );
+        assert!(message.contains("\"if\" is matched by `If`"), "{message}");
+    }
+
+    #[test]
+    fn not_total() {
+        let message = error(quote! {
+            #[herring(total)]
+            #[herring(skip " +")]
+            enum Token {
+                #[regex("[a-z]+")]
+                Ident,
+                #[regex(r#""[^"]*""#)]
+                Str,
+            }
+        });
+        assert_eq!(
+            message,
+            "the lexer is not total, e.g. \"!\" cannot be split into tokens"
+        );
+        let message = error(quote! {
+            #[herring(total)]
+            enum Token {
+                #[regex("[a-z]+")]
+                Ident,
+                #[regex(r#""[^"]*""#)]
+                Str,
+                #[regex(r#"[^a-z"]"#)]
+                Other,
+            }
+        });
+        assert_eq!(
+            message,
+            "the lexer is not total, e.g. \"\\\"\" cannot be split into tokens"
+        );
+    }
+
+    /// Maps the range in the value of the literal to the source of the literal.
+    fn source_range(lit: &str, start: usize, end: usize) -> Option<String> {
+        let source = syn::parse_str::<LitStr>(lit).unwrap().token().to_string();
+        let range = source_offset(&source, start)?..source_offset(&source, end)?;
+        Some(source[range].to_string())
+    }
+
+    #[test]
+    fn source_offset_plain() {
+        assert_eq!(source_range(r#""[a-z]+""#, 1, 4), Some("a-z".to_string()));
+        assert_eq!(source_range(r#""ab""#, 0, 2), Some("ab".to_string()));
+        assert_eq!(source_range(r#""é[""#, 2, 3), Some("[".to_string()));
+        assert_eq!(source_range(r#""ab""#, 0, 3), None);
+    }
+
+    #[test]
+    fn source_offset_raw() {
+        assert_eq!(source_range(r#"r"\d+""#, 0, 2), Some(r"\d".to_string()));
+        assert_eq!(
+            source_range(r###"r##"a"#[b"##"###, 3, 5),
+            Some("[b".to_string())
+        );
+    }
+
+    #[test]
+    fn source_offset_escapes() {
+        assert_eq!(source_range(r#""\\d+""#, 0, 2), Some(r"\\d".to_string()));
+        assert_eq!(source_range(r#""\x41[b""#, 1, 3), Some("[b".to_string()));
+        assert_eq!(
+            source_range(r#""\u{e9}\u{1F600}[""#, 6, 7),
+            Some("[".to_string())
+        );
+        assert_eq!(
+            source_range(r#""\u{1F600}""#, 0, 4),
+            Some(r"\u{1F600}".to_string())
+        );
+        assert_eq!(source_range(r#""\"a\"""#, 1, 2), Some("a".to_string()));
+    }
+
+    #[test]
+    fn source_offset_line_continuation() {
+        assert_eq!(
+            source_range("\"ab\\\n    [c\"", 2, 4),
+            Some("[c".to_string())
+        );
+        assert_eq!(
+            source_range("\"ab\\\r\n\t[c\"", 2, 3),
+            Some("[".to_string())
+        );
+    }
+
+    #[test]
+    fn error_in_subpattern() {
+        let message = error(quote! {
+            #[herring(subpattern digits = "[0-9]{2,1}")]
+            enum Token {
+                #[regex("x(?&digits)")]
+                Number,
+            }
+        });
+        assert!(
+            message.starts_with("regex parse error in subpattern `digits`"),
+            "{message}"
+        );
+        assert!(
+            message.contains("invalid repetition count range"),
+            "{message}"
+        );
+        assert!(
+            message.ends_with("subpattern `digits` is used by this regex"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn state_budget() {
+        let message = error(quote! {
+            #[herring(max_states = 8)]
+            enum Token {
+                #[regex("[a-z]{10}")]
+                Word,
+                #[token("x")]
+                X,
+            }
+        });
+        assert!(
+            message.starts_with("the DFA exceeds the limit of 8 states"),
+            "{message}"
+        );
+        assert!(
+            message.ends_with(
+                "`Word` needs more than 8 states on its own`X` needs 2 states on its own"
+            ),
+            "{message}"
+        );
+    }
+
+    /// Returns the messages of the errors with the source text they point to.
+    fn error_locations(source: &str) -> Vec<(String, String)> {
+        match generate_impl(source.parse().unwrap()) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err
+                .into_iter()
+                .map(|err| {
+                    let text = err.span().source_text().unwrap_or_default();
+                    (err.to_string(), text)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn error_in_lookahead() {
+        let errors = error_locations(
+            r#"
+            enum Token {
+                #[regex("a+(?=b{2,1})")]
+                A,
+                #[regex("c+", lookahead = "d{2,1}")]
+                C,
+                #[regex("e+", except = ["ee", "e{3,2}"])]
+                E,
+                #[regex("f+", and = r"f(?&g)")]
+                F,
+            }
+            "#,
+        );
+        let locations = errors
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(locations, ["{2,1}", "{2,1}", "{3,2}", "(?&g)"]);
+        assert!(
+            errors[3].0.contains("undefined subpattern `g`"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn conflict() {
+        let errors = error_locations(
+            r#"
+            enum Token {
+                #[regex("[a-z]+")]
+                Ident,
+                #[regex("[a-c]+")]
+                Abc,
+            }
+            "#,
+        );
+        let locations = errors
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(locations, [r#""[a-z]+""#, r#""[a-c]+""#]);
+        assert_eq!(
+            errors[0].0,
+            "tokens `Ident` and `Abc` both have priority 2 and match the same word \"a\"\n\
+             priority of `Ident`: 2 = 1 × (2 for `[a-z]`)\n\
+             priority of `Abc`: 2 = 1 × (2 for `[a-c]`)"
+        );
+        assert_eq!(errors[1].0, "`Abc` also matches \"a\" with priority 2");
+    }
+
+    #[test]
+    fn independent_errors() {
+        let source = r#"
+            #[herring(skip " +", priority = "high")]
+            #[herring(subpattern digits "[0-9]+")]
+            enum Token {
+                #[regex("[a-z]+", priority = 3, priority = 4)]
+                Ident,
+                #[token(".")]
+                Dot,
+            }
+            "#;
+        let errors = error_locations(source);
+        assert_eq!(
+            errors,
+            [
+                ("expected integer literal", r#""high""#),
+                ("expected `=`", r#""[0-9]+""#),
+                ("`priority` was already specified", "priority"),
+            ]
+            .map(|(message, text)| (message.to_string(), text.to_string()))
+        );
+        // the duplicate is located at the second key
+        let duplicate = match generate_impl(source.parse().unwrap()) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.into_iter().nth(2).unwrap().span().start(),
+        };
+        let line = source.lines().nth(duplicate.line - 1).unwrap();
+        assert_eq!(duplicate.column, line.rfind("priority").unwrap());
+    }
+}
@@ -1,28 +1,103 @@
+use crate::cache::CacheEntry;
+use crate::debug::{DebugModes, Stats};
+use crate::diagnostics::{conflict_error, format_word, shadowed_error, state_budget_error};
 use crate::parse::*;
-use herring_automata::{Dfa, Nfa, Output, Pattern, State, StateRef, Transition};
+use herring_automata::{
+    ByteKind, CaptureInst, Captures, Dfa, Nfa, Output, Pattern, State, StateRef, TokenEnd,
+    Transition,
+};
 use proc_macro2::{Span, TokenStream};
-use quote::quote;
-use std::collections::{BTreeMap, HashMap};
+use quote::{quote, quote_spanned};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use syn::spanned::Spanned;
 use syn::{Error, Expr, Ident};
 
 pub(crate) const SKIP_NAME: &str = "skipped regex";
 
-fn generate_dfa(tokens: Vec<herring_automata::Token>, enum_name: &Ident) -> syn::Result<Dfa> {
-    let nfa = Nfa::new_tokenizer(tokens);
-    crate::debug::graph(&nfa, &format!("{enum_name}_nfa"))?;
+fn generate_dfa(
+    tokens: &[TokenAttr],
+    enum_attrs: &EnumAttrs,
+    enum_name: &Ident,
+    debug: &DebugModes,
+    stats: &mut Stats,
+) -> syn::Result<Dfa> {
+    let subpatterns = &enum_attrs.subpatterns;
+    crate::debug::overlaps(tokens, debug, &enum_name.to_string())?;
+    // every attribute is its own output during the subset construction, so conflicts and
+    // unreachable attributes are found even among the attributes of a variant
+    let nfa = Nfa::new_tokenizer(
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(i, attr)| {
+                let value = (attr.token.value().0.clone(), i);
+                attr.token.clone().with_value(value)
+            })
+            .collect(),
+    );
+    debug.graph(&nfa, &format!("{enum_name}_nfa"))?;
+    stats.count("NFA states", nfa.states().len());
+    stats.phase("NFA construction");
 
-    let subset_dfa = match nfa.into_dfa() {
-        Ok(dfa) => dfa,
-        Err(err) => return Err(Error::new(Span::call_site(), err.message)),
+    let max_states = enum_attrs
+        .max_states
+        .map_or(usize::MAX, |(max_states, _)| max_states);
+    let values = tokens
+        .iter()
+        .map(|attr| attr.token.value())
+        .collect::<Vec<_>>();
+    let cache_entry = CacheEntry::new(&nfa, max_states, &values, debug);
+    if let Some(dfa) = cache_entry.as_ref().and_then(CacheEntry::load) {
+        return Ok(dfa);
+    }
+    let subset_dfa = match nfa.into_dfa_within(max_states) {
+        Ok(Some(dfa)) => dfa,
+        Ok(None) => {
+            let (max_states, span) = enum_attrs.max_states.unwrap();
+            return Err(state_budget_error(max_states, span, tokens));
+        }
+        Err(err) => {
+            return Err(err
+                .conflict
+                .and_then(|conflict| conflict_error(&conflict, tokens, subpatterns))
+                .unwrap_or_else(|| Error::new(Span::call_site(), err.message)));
+        }
     };
-    crate::debug::graph(&subset_dfa, &format!("{enum_name}_dfa"))?;
+    if let Some(err) = shadowed_error(&subset_dfa, tokens) {
+        return Err(err);
+    }
+    let subset_dfa = subset_dfa.map_values(|(_, i)| tokens[*i].token.value().clone());
+    debug.graph(&subset_dfa, &format!("{enum_name}_dfa"))?;
+    stats.count("subset DFA states", subset_dfa.states().len());
+    stats.phase("subset construction");
 
     let minimal_dfa = subset_dfa.into_minimized();
-    crate::debug::graph(&minimal_dfa, &format!("{enum_name}_min"))?;
+    debug.graph(&minimal_dfa, &format!("{enum_name}_min"))?;
+    stats.count("minimized DFA states", minimal_dfa.states().len());
+    stats.phase("minimization");
 
+    if let Some(cache_entry) = cache_entry {
+        cache_entry.store(&minimal_dfa);
+    }
     Ok(minimal_dfa)
 }
 
+/// Checks that every input can be split into tokens, if the lexer is required to be total.
+fn check_total(dfa: &Dfa, total: Option<Span>, binary: bool) -> syn::Result<()> {
+    if let Some(span) = total {
+        if let Some(word) = dfa.shortest_unlexable(!binary) {
+            return Err(Error::new(
+                span,
+                format!(
+                    "the lexer is not total, e.g. {} cannot be split into tokens",
+                    format_word(&word)
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
 macro_rules! ident {
     ($fmt:literal) => {
         Ident::new(&format!($fmt), Span::call_site())
@@ -89,8 +164,36 @@ fn generate_pattern<'a>(
     }
 }
 
+fn generate_kind_pattern(kind: ByteKind) -> TokenStream {
+    kind.pattern()
+        .map_or(quote! { None }, |pattern| generate_byte_pattern(&pattern))
+}
+
+/// Selects the start state by the kind of the byte before the token, if the DFA depends on it.
+fn generate_start_state(dfa: &Dfa) -> TokenStream {
+    if dfa.starts().is_empty() {
+        return quote! { State::S0 };
+    }
+    let mut arms = vec![];
+    for (kind, start) in ByteKind::ALL.into_iter().zip(dfa.starts()) {
+        let pattern = generate_kind_pattern(kind);
+        let state_ident = ident!("S{}", start.value());
+        arms.push(quote! { #pattern => State::#state_ident, });
+    }
+    quote! {{
+        use herring::Source;
+        match lexer
+            .start
+            .checked_sub(1)
+            .and_then(|offset| lexer.source.get_byte(offset))
+        {
+            #(#arms)*
+        }
+    }}
+}
+
 fn generate_eof_jump(dfa: &Dfa, state_ref: StateRef) -> TokenStream {
-    if dfa.start() == state_ref {
+    if dfa.start() == state_ref || dfa.starts().contains(&state_ref) {
         quote! {
             lexer.offset -= 1;
             return None;
@@ -120,6 +223,7 @@ fn generate_pattern_transitions<'a>(
     state_ref: StateRef,
     state: &'a State,
     luts: &mut BTreeMap<&'a Pattern, usize>,
+    next_byte: TokenStream,
 ) -> TokenStream {
     let mut transitions = vec![];
     for transition in state.transitions().iter() {
@@ -134,7 +238,7 @@ fn generate_pattern_transitions<'a>(
     let eof_jump = generate_eof_jump(dfa, state_ref);
     transitions.push(quote! { None => { #eof_jump } });
     quote! {
-        match lexer.next_byte() {
+        match #next_byte {
             #(#transitions)*
             _ => {
                 let offset = lexer.offset - 1;
@@ -147,7 +251,12 @@ fn generate_pattern_transitions<'a>(
     }
 }
 
-fn generate_lut_transitions(dfa: &Dfa, state_ref: StateRef, state: &State) -> TokenStream {
+fn generate_lut_transitions(
+    dfa: &Dfa,
+    state_ref: StateRef,
+    state: &State,
+    next_byte: TokenStream,
+) -> TokenStream {
     let mut entries = vec![];
     'outer: for b in u8::MIN..=u8::MAX {
         for t in state.transitions().iter() {
@@ -178,7 +287,7 @@ fn generate_lut_transitions(dfa: &Dfa, state_ref: StateRef, state: &State) -> To
                 #(#entries),*
             ]
         };
-        if let Some(byte) = lexer.next_byte() {
+        if let Some(byte) = #next_byte {
             match LUT[byte as usize] {
                 #(Jumps::#targets => {
                     #jumps
@@ -196,46 +305,134 @@ fn generate_lut_transitions(dfa: &Dfa, state_ref: StateRef, state: &State) -> To
     }
 }
 
+/// States with at least three transitions, of which one is taken by a range of bytes, jump by a
+/// table indexed by the next byte.
+fn uses_jump_table(state: &State) -> bool {
+    state.transitions().len() >= 3
+        && state
+            .transitions()
+            .iter()
+            .any(|t| t.when().ranges().iter().any(|p| p.start() != p.end()))
+}
+
 fn generate_transitions<'a>(
     dfa: &'a Dfa,
     state_ref: StateRef,
     state: &'a State,
     luts: &mut BTreeMap<&'a Pattern, usize>,
+    trace: bool,
 ) -> TokenStream {
-    if state.transitions().len() >= 3
-        && state
-            .transitions()
-            .iter()
-            .any(|t| t.when().ranges().iter().any(|p| p.start() != p.end()))
-    {
-        generate_lut_transitions(dfa, state_ref, state)
+    let next_byte = if trace {
+        quote! { lexer.next_byte_traced() }
     } else {
-        generate_pattern_transitions(dfa, state_ref, state, luts)
+        quote! { lexer.next_byte() }
+    };
+    if uses_jump_table(state) {
+        generate_lut_transitions(dfa, state_ref, state, next_byte)
+    } else {
+        generate_pattern_transitions(dfa, state_ref, state, luts, next_byte)
     }
 }
 
-fn generate_callback_def(
+/// Defines the callbacks, whose return values are converted, so all callbacks have the same type.
+fn generate_callback_defs(
     enum_name: &Ident,
+    enum_ty: &TokenStream,
+    callbacks: HashMap<(String, usize), Expr>,
+    fields: &HashSet<String>,
+) -> HashMap<(String, usize), TokenStream> {
+    let mut callback_defs = HashMap::new();
+    for (value, callback) in callbacks {
+        let (callback_ty, into_result) = if value.0 == SKIP_NAME {
+            (
+                quote! { _ },
+                quote_spanned! {callback.span()=>
+                    herring::SkipCallbackResult::into_result(callback(lexer))
+                },
+            )
+        } else if fields.contains(&value.0) {
+            // the value returned by the callback fills the field of the variant
+            let enumerator = ident!(value.0);
+            (
+                quote! { Result<_, <Self as Herring<'source>>::Error> },
+                quote_spanned! {callback.span()=>
+                    Some(callback(lexer).map(#enum_name::#enumerator))
+                },
+            )
+        } else {
+            let enumerator = ident!(value.0);
+            (
+                quote! { _ },
+                quote_spanned! {callback.span()=>
+                    herring::CallbackResult::into_result(callback(lexer), #enum_name::#enumerator)
+                },
+            )
+        };
+        callback_defs.insert(
+            value,
+            quote! {
+                let callback: fn(
+                    &mut herring::Lexer<'source, #enum_ty>
+                ) -> Option<Result<#enum_ty, <Self as Herring<'source>>::Error>> = |lexer| {
+                    let callback: fn(&mut herring::Lexer<'source, #enum_ty>) -> #callback_ty = #callback;
+                    #into_result
+                };
+            },
+        );
+    }
+    callback_defs
+}
+
+fn generate_callback_def(
     output: &Option<Output>,
-    callbacks: &HashMap<(String, usize), Expr>,
-    is_skip: bool,
+    callback_defs: &HashMap<(String, usize), TokenStream>,
 ) -> TokenStream {
-    if let Some(output) = output {
-        if let Some(callback) = callbacks.get(output.value()) {
-            return if is_skip {
-                quote! {
-                    let callback: fn(&mut herring::Lexer<'source, #enum_name>) = #callback;
-                }
-            } else {
-                quote! {
-                    let callback: fn(
-                        &mut herring::Lexer<'source, #enum_name>
-                    ) -> Result<#enum_name, <Self as Herring<'source>>::Error> = #callback;
-                }
-            };
+    output
+        .as_ref()
+        .and_then(|output| callback_defs.get(output.value()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Calls a hook of the tracer, if the lexer is generated with tracing.
+///
+/// The arguments are evaluated before the tracer borrows the lexer.
+fn generate_trace(trace: bool, hook: &str, args: &[TokenStream]) -> TokenStream {
+    if !trace {
+        return quote! {};
+    }
+    let hook = ident!("{hook}");
+    let vars = (0..args.len())
+        .map(|i| ident!("arg{i}"))
+        .collect::<Vec<_>>();
+    quote! {{
+        #(let #vars = #args;)*
+        if let Some(tracer) = lexer.tracer() {
+            tracer.#hook(#(#vars),*);
         }
+    }}
+}
+
+fn generate_token_end(output: &Output) -> TokenStream {
+    match output.end() {
+        TokenEnd::MatchEnd => quote! { lexer.offset },
+        TokenEnd::BeforeMatchEnd(len) => quote! { lexer.offset - #len },
+        TokenEnd::AfterMatchStart(len) => quote! { lexer.start + #len },
+    }
+}
+
+/// Remembers the capture program of the accepted token, if any token has captures.
+fn generate_last_captures(
+    output: &Output,
+    captures: &HashMap<(String, usize), Ident>,
+) -> TokenStream {
+    if captures.is_empty() {
+        quote! {}
+    } else if let Some(ident) = captures.get(output.value()) {
+        quote! { last_captures = Some(&#ident); }
+    } else {
+        quote! { last_captures = None; }
     }
-    quote! {}
 }
 
 fn generate_last_accept(
@@ -243,52 +440,155 @@ fn generate_last_accept(
     output: &Option<Output>,
     enum_name: &Ident,
     is_skip: bool,
+    captures: &HashMap<(String, usize), Ident>,
+    trace: bool,
 ) -> TokenStream {
     if let Some(output) = output {
-        if callback_def.is_empty() {
+        let end = generate_token_end(output);
+        let last_captures = generate_last_captures(output, captures);
+        let name = output.value().0.as_str();
+        let trace_accept = if trace {
+            let accept_recorded =
+                generate_trace(trace, "accept_recorded", &[quote! { #name }, end.clone()]);
+            quote! {
+                last_token = #name;
+                #accept_recorded
+            }
+        } else {
+            quote! {}
+        };
+        let last_accept = if callback_def.is_empty() {
             if is_skip {
-                quote! { last_accept = LastAccept::Skip(lexer.offset); }
+                quote! { last_accept = LastAccept::Skip(#end); }
             } else {
                 let enumerator = ident!(output.value().0);
-                quote! { last_accept = LastAccept::Token(#enum_name::#enumerator, lexer.offset); }
+                quote! { last_accept = LastAccept::Token(#enum_name::#enumerator, #end); }
             }
-        } else if is_skip {
-            quote! { last_accept = LastAccept::SkipCallback(callback, lexer.offset); }
         } else {
-            quote! { last_accept = LastAccept::TokenCallback(callback, lexer.offset); }
+            quote! { last_accept = LastAccept::Callback(callback, #end); }
+        };
+        quote! {
+            #last_accept
+            #last_captures
+            #trace_accept
         }
     } else {
         quote! {}
     }
 }
 
+fn is_skip(output: &Option<Output>) -> bool {
+    output
+        .as_ref()
+        .is_some_and(|output| output.value().0 == SKIP_NAME)
+}
+
+/// Records the accepted token by the kind of the next byte, if acceptance depends on it.
+fn generate_next_accept(
+    outputs: &[Option<Output>],
+    enum_name: &Ident,
+    callback_defs: &HashMap<(String, usize), TokenStream>,
+    captures: &HashMap<(String, usize), Ident>,
+    trace: bool,
+) -> TokenStream {
+    let mut arms = vec![];
+    for (kind, output) in ByteKind::ALL.into_iter().zip(outputs) {
+        if output.is_none() {
+            continue;
+        }
+        let pattern = generate_kind_pattern(kind);
+        let is_skip = is_skip(output);
+        let callback_def = generate_callback_def(output, callback_defs);
+        let last_accept =
+            generate_last_accept(&callback_def, output, enum_name, is_skip, captures, trace);
+        arms.push(quote! {
+            #pattern => {
+                #callback_def
+                #last_accept
+            }
+        });
+    }
+    if outputs.iter().any(Option::is_none) {
+        arms.push(quote! { _ => {} });
+    }
+    quote! {{
+        use herring::Source;
+        match lexer.source.get_byte(lexer.offset) {
+            #(#arms)*
+        }
+    }}
+}
+
 fn generate_final_state_branch(
     enum_name: &Ident,
     output: &Output,
     callback_def: TokenStream,
     is_skip: bool,
-    state_ident: Ident,
-    log_state: TokenStream,
+    num: usize,
+    captures: &HashMap<(String, usize), Ident>,
+    trace: bool,
 ) -> TokenStream {
+    let state_ident = ident!("S{num}");
+    let name = output.value().0.as_str();
+    let end = generate_token_end(output);
+    let trace_state = generate_trace(
+        trace,
+        "state_enter",
+        &[quote! { #num }, quote! { lexer.offset }],
+    );
+    let trace_accept = generate_trace(trace, "accept_recorded", &[quote! { #name }, end.clone()]);
+    let trace_emit = generate_trace(
+        trace,
+        "token_emitted",
+        &[quote! { Some(#name) }, quote! { lexer.span() }],
+    );
     let jump = if callback_def.is_empty() {
         if is_skip {
             quote! { continue 'skip; }
         } else {
             let enumerator = ident!(output.value().0);
-            quote! { return Some(Ok(#enum_name::#enumerator)); }
+            quote! {
+                #trace_emit
+                return Some(Ok(#enum_name::#enumerator));
+            }
         }
-    } else if is_skip {
+    } else {
+        let emit = if trace {
+            quote! {{
+                #trace_emit
+                return Some(result);
+            }}
+        } else {
+            quote! { return Some(result) }
+        };
         quote! {
-            callback(lexer);
-            continue 'skip;
+            match callback(lexer) {
+                Some(result) => #emit,
+                None => continue 'skip,
+            }
         }
+    };
+    let set_end = match output.end() {
+        TokenEnd::MatchEnd => quote! {},
+        TokenEnd::BeforeMatchEnd(len) => quote! { lexer.offset -= #len; },
+        TokenEnd::AfterMatchStart(len) => quote! { lexer.offset = lexer.start + #len; },
+    };
+    let trace_backtrack = if set_end.is_empty() {
+        quote! {}
     } else {
-        quote! { return Some(callback(lexer)); }
+        generate_trace(trace, "backtrack", &[quote! { lexer.offset }, end])
     };
+    let set_captures = captures
+        .get(output.value())
+        .map_or(quote! {}, |ident| quote! { lexer.set_captures(&#ident); });
     quote! {
         State::#state_ident => {
             #callback_def
-            #log_state
+            #trace_state
+            #trace_accept
+            #trace_backtrack
+            #set_end
+            #set_captures
             #jump
         }
     }
@@ -297,57 +597,78 @@ fn generate_final_state_branch(
 fn generate_state_branches<'a>(
     dfa: &'a Dfa,
     enum_name: &Ident,
-    callbacks: HashMap<(String, usize), Expr>,
+    callback_defs: HashMap<(String, usize), TokenStream>,
+    captures: &HashMap<(String, usize), Ident>,
     luts: &mut BTreeMap<&'a Pattern, usize>,
+    trace: bool,
 ) -> syn::Result<Vec<TokenStream>> {
     let mut branches = vec![];
     for (num, state) in dfa.states().iter().enumerate() {
         let state_ref = StateRef::new(num);
         let state_ident = ident!("S{num}");
-        let log_state = crate::debug::log_state(num);
+        let trace_state = generate_trace(
+            trace,
+            "state_enter",
+            &[quote! { #num }, quote! { lexer.offset }],
+        );
         let output = dfa.accepts().get(&state_ref).unwrap_or(&None);
-        let is_skip = output
-            .as_ref()
-            .is_some_and(|output| output.value().0 == SKIP_NAME);
-        let callback_def = generate_callback_def(enum_name, output, &callbacks, is_skip);
-
-        branches.push(if !state.transitions().is_empty() {
-            let last_accept = generate_last_accept(&callback_def, output, enum_name, is_skip);
-            let transitions = generate_transitions(dfa, state_ref, state, luts);
-            let loop_edge = if state.transitions().iter().any(|t| t.to() == state_ref) {
-                quote! { loop }
+        let is_skip = is_skip(output);
+        let callback_def = generate_callback_def(output, &callback_defs);
+        let next_accept = dfa.next_accepts().get(&state_ref).map(|outputs| {
+            generate_next_accept(outputs, enum_name, &callback_defs, captures, trace)
+        });
+
+        branches.push(
+            if let (true, Some(output)) = (state.transitions().is_empty(), output) {
+                generate_final_state_branch(
+                    enum_name,
+                    output,
+                    callback_def,
+                    is_skip,
+                    num,
+                    captures,
+                    trace,
+                )
+            } else if let (true, Some(next_accept)) = (state.transitions().is_empty(), &next_accept)
+            {
+                quote! {
+                    State::#state_ident => {
+                        #trace_state
+                        #next_accept
+                        break 'fsm;
+                    }
+                }
             } else {
-                quote! {}
-            };
-            quote! {
-                State::#state_ident => {
-                    #callback_def
-                    #loop_edge {
-                        #log_state
-                        #last_accept
-                        #transitions
+                // states without transitions or outputs can occur at assertions that never hold and
+                // only reject the input
+                let last_accept = next_accept.unwrap_or_else(|| {
+                    generate_last_accept(&callback_def, output, enum_name, is_skip, captures, trace)
+                });
+                let transitions = generate_transitions(dfa, state_ref, state, luts, trace);
+                let loop_edge = if state.transitions().iter().any(|t| t.to() == state_ref) {
+                    quote! { loop }
+                } else {
+                    quote! {}
+                };
+                quote! {
+                    State::#state_ident => {
+                        #callback_def
+                        #loop_edge {
+                            #trace_state
+                            #last_accept
+                            #transitions
+                        }
                     }
                 }
-            }
-        } else if let Some(output) = output {
-            generate_final_state_branch(
-                enum_name,
-                output,
-                callback_def,
-                is_skip,
-                state_ident,
-                log_state,
-            )
-        } else {
-            panic!("non-accepting state has no outgoing transitions, please report this bug")
-        });
+            },
+        );
     }
     Ok(branches)
 }
 
 fn generate_stacked_lut_defs(luts: BTreeMap<&Pattern, usize>) -> Vec<TokenStream> {
     let mut lut_defs = vec![];
-    let mut tables = vec![[0u8; 256]; (luts.len() + 7) / 8];
+    let mut tables = vec![[0u8; 256]; luts.len().div_ceil(8)];
     for patterns in luts.into_iter().collect::<Vec<_>>().chunks(8) {
         for (p, i) in patterns.iter() {
             for b in u8::MIN..=u8::MAX {
@@ -362,12 +683,70 @@ fn generate_stacked_lut_defs(luts: BTreeMap<&Pattern, usize>) -> Vec<TokenStream
     lut_defs
 }
 
+/// Generates the capture programs, which are identified by the output of their token.
+fn generate_capture_defs(
+    captures: BTreeMap<(String, usize), Captures>,
+) -> (Vec<TokenStream>, HashMap<(String, usize), Ident>) {
+    let mut capture_defs = vec![];
+    let mut capture_idents = HashMap::new();
+    for (num, (value, captures)) in captures.into_iter().enumerate() {
+        let ident = ident!("CAPTURES{num}");
+        let names = captures.names();
+        let insts = captures.insts().iter().map(|inst| match inst {
+            CaptureInst::Ranges(ranges, next) => {
+                let (starts, ends): (Vec<_>, Vec<_>) = ranges.iter().copied().unzip();
+                quote! { herring::CaptureInst::Ranges(&[#((#starts, #ends)),*], #next) }
+            }
+            CaptureInst::Split(first, second) => {
+                quote! { herring::CaptureInst::Split(#first, #second) }
+            }
+            CaptureInst::Jump(next) => quote! { herring::CaptureInst::Jump(#next) },
+            CaptureInst::Save(register) => quote! { herring::CaptureInst::Save(#register) },
+            CaptureInst::Match => quote! { herring::CaptureInst::Match },
+        });
+        capture_defs.push(quote! {
+            static #ident: herring::Captures = herring::Captures {
+                names: &[#(#names),*],
+                insts: &[#(#insts),*],
+            };
+        });
+        capture_idents.insert(value, ident);
+    }
+    (capture_defs, capture_idents)
+}
+
 pub(crate) fn generate_impl(tokens: TokenStream) -> syn::Result<TokenStream> {
+    generate_with_stats(tokens, &mut Stats::new())
+}
+
+/// Generates the lexer and records its statistics, which are printed for the `stats` debug mode.
+pub(crate) fn generate_with_stats(
+    tokens: TokenStream,
+    stats: &mut Stats,
+) -> syn::Result<TokenStream> {
     let token_enum = parse_enum(tokens)?;
+    stats.phase("parsing");
     let enum_name = token_enum.name;
     let enum_attrs = token_enum.attrs;
     let enum_variants = token_enum.variants;
-    let dfa = generate_dfa(enum_variants.tokens, &enum_name)?;
+    let errors = token_enum.errors;
+    let debug = DebugModes::new(&enum_attrs.debug);
+    // the lifetime is also a parameter of the local `LastAccept` enum, which stores a token
+    let (enum_ty, lifetime_param) = if token_enum.lifetime {
+        (quote! { #enum_name<'source> }, quote! { 'source, })
+    } else {
+        (quote! { #enum_name }, quote! {})
+    };
+    // the automaton is also built, if attributes have errors, to report conflicts among the
+    // other attributes
+    let dfa = errors.finish(generate_dfa(
+        &enum_variants.tokens,
+        &enum_attrs,
+        &enum_name,
+        &debug,
+        stats,
+    ))?;
+    check_total(&dfa, enum_attrs.total, token_enum.binary)?;
 
     let states = (0..dfa.states().len())
         .map(|i| {
@@ -376,22 +755,88 @@ pub(crate) fn generate_impl(tokens: TokenStream) -> syn::Result<TokenStream> {
         })
         .collect::<Vec<_>>();
 
+    let start_state = generate_start_state(&dfa);
+    let (capture_defs, capture_idents) =
+        generate_capture_defs(enum_variants.captures.into_iter().collect());
+    let callback_defs = generate_callback_defs(
+        &enum_name,
+        &enum_ty,
+        enum_variants.callbacks,
+        &enum_variants.fields,
+    );
     let mut luts = BTreeMap::new();
-    let branches = generate_state_branches(&dfa, &enum_name, enum_variants.callbacks, &mut luts)?;
+    let branches = generate_state_branches(
+        &dfa,
+        &enum_name,
+        callback_defs,
+        &capture_idents,
+        &mut luts,
+        enum_attrs.trace,
+    )?;
+    let (clear_captures, last_captures_def, set_last_captures) = if capture_idents.is_empty() {
+        (quote! {}, quote! {}, quote! {})
+    } else {
+        (
+            quote! { lexer.clear_captures(); },
+            quote! { let mut last_captures: Option<&'static herring::Captures> = None; },
+            quote! {
+                if let Some(captures) = last_captures {
+                    lexer.set_captures(captures);
+                }
+            },
+        )
+    };
 
+    let (last_token_def, trace_backtrack, trace_emit, trace_error) = if enum_attrs.trace {
+        (
+            quote! { let mut last_token = ""; },
+            {
+                let backtrack = generate_trace(
+                    true,
+                    "backtrack",
+                    &[quote! { lexer.offset }, quote! { offset }],
+                );
+                quote! {
+                    if offset != lexer.offset {
+                        #backtrack
+                    }
+                }
+            },
+            generate_trace(
+                true,
+                "token_emitted",
+                &[quote! { Some(last_token) }, quote! { lexer.span() }],
+            ),
+            generate_trace(
+                true,
+                "token_emitted",
+                &[quote! { None }, quote! { lexer.span() }],
+            ),
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
+    stats.count(
+        "jump table states",
+        dfa.states().iter().filter(|s| uses_jump_table(s)).count(),
+    );
+    stats.count("LUT patterns", luts.len());
+    // the patterns are stacked as bits of the tables
+    stats.count("lookup tables", luts.len().div_ceil(8));
     let lut_defs = generate_stacked_lut_defs(luts);
     let ignore_call = enum_attrs.ignore_cb.map_or(quote! {}, |callback| {
         quote! {
             use herring::Source;
-            let callback: fn(&mut herring::Lexer<'source, #enum_name>) = #callback;
+            let callback: fn(&mut herring::Lexer<'source, #enum_ty>) = #callback;
             callback(lexer)
         }
     });
     let initial_call = enum_attrs.initial_cb.map_or(quote! {}, |callback| {
         quote! {
             let callback: fn(
-                &mut herring::Lexer<'source, #enum_name>
-            ) -> Option<Result<#enum_name, <Self as Herring<'source>>::Error>> = #callback;
+                &mut herring::Lexer<'source, #enum_ty>
+            ) -> Option<Result<#enum_ty, <Self as Herring<'source>>::Error>> = #callback;
             if let Some(tok) = callback(lexer) {
                 return Some(tok);
             }
@@ -412,40 +857,42 @@ pub(crate) fn generate_impl(tokens: TokenStream) -> syn::Result<TokenStream> {
     );
     let lexer_impl = quote! {
         #[allow(dead_code, unused_imports, unused_labels, clippy::type_complexity)]
-        impl<'source> Herring<'source> for #enum_name {
+        impl<'source> Herring<'source> for #enum_ty {
             type Error = #error_ty;
             type Extras = #extras_ty;
             type Source = #source_ty;
 
             #[inline]
-            fn ignore(lexer: &mut herring::Lexer<'source, #enum_name>) { #ignore_call }
+            fn ignore(lexer: &mut herring::Lexer<'source, #enum_ty>) { #ignore_call }
             #[inline]
             fn lex(
-                lexer: &mut herring::Lexer<'source, #enum_name>
-            ) -> Option<Result<#enum_name, <Self as Herring<'source>>::Error>> {
-                enum LastAccept<TokenCallback, SkipCallback> {
+                lexer: &mut herring::Lexer<'source, #enum_ty>
+            ) -> Option<Result<#enum_ty, <Self as Herring<'source>>::Error>> {
+                enum LastAccept<#lifetime_param Callback> {
                     None,
-                    Token(#enum_name, usize),
-                    TokenCallback(TokenCallback, usize),
+                    Token(#enum_ty, usize),
+                    Callback(Callback, usize),
                     Skip(usize),
-                    SkipCallback(SkipCallback, usize),
                 }
                 enum State {
                     #(#states,)*
                 }
                 #(#lut_defs)*
+                #(#capture_defs)*
 
                 'skip: loop {
                     lexer.start = lexer.offset;
+                    #clear_captures
                     #initial_call
 
-                    let mut state = State::S0;
+                    let mut state = #start_state;
                     let mut last_accept: LastAccept<
                         fn(
-                            &mut herring::Lexer<'source, #enum_name>
-                        ) -> Result<#enum_name, <Self as Herring<'source>>::Error>,
-                        fn(&mut herring::Lexer<'source, #enum_name>)
+                            &mut herring::Lexer<'source, #enum_ty>
+                        ) -> Option<Result<#enum_ty, <Self as Herring<'source>>::Error>>
                     > = LastAccept::None;
+                    #last_captures_def
+                    #last_token_def
                     'fsm: loop {
                         match state {
                             #(#branches)*
@@ -457,27 +904,79 @@ pub(crate) fn generate_impl(tokens: TokenStream) -> syn::Result<TokenStream> {
                             while !lexer.source.is_boundary(lexer.offset) {
                                 lexer.offset += 1;
                             }
+                            #trace_error
                             return Some(Err(Default::default()));
                         }
                         LastAccept::Token(token, offset) => {
+                            #trace_backtrack
                             lexer.offset = offset;
+                            #set_last_captures
+                            #trace_emit
                             return Some(Ok(token));
                         }
-                        LastAccept::TokenCallback(callback, offset) => {
+                        LastAccept::Callback(callback, offset) => {
+                            #trace_backtrack
                             lexer.offset = offset;
-                            return Some(callback(lexer));
+                            #set_last_captures
+                            if let Some(result) = callback(lexer) {
+                                #trace_emit
+                                return Some(result);
+                            }
                         }
                         LastAccept::Skip(offset) => {
+                            #trace_backtrack
                             lexer.offset = offset;
                         }
-                        LastAccept::SkipCallback(callback, offset) => {
-                            lexer.offset = offset;
-                            callback(lexer);
-                        }
                     }
                 }
             }
         }
     };
-    crate::debug::expand_or_skip(lexer_impl, &enum_name)
+    stats.phase("code generation");
+    stats.print(&enum_name, &debug);
+    crate::debug::expand_or_skip(lexer_impl, &enum_name, &debug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_impl;
+    use quote::quote;
+
+    #[test]
+    fn deterministic() {
+        let lexer = |reversed: bool| {
+            let (first, second) = (
+                quote! { #[herring(subpattern digit = "[0-9]")] },
+                quote! { #[herring(subpattern alpha = "[a-z]")] },
+            );
+            let (first, second) = if reversed {
+                (second, first)
+            } else {
+                (first, second)
+            };
+            generate_impl(quote! {
+                #first
+                #second
+                #[herring(skip " +")]
+                enum Token {
+                    #[regex("(?&alpha)+", |lex| lex.slice().len())]
+                    Ident,
+                    #[regex("(?<int>(?&digit)+)(?:[.](?<frac>(?&digit)+))?")]
+                    Number,
+                    #[token("if", priority = 5)]
+                    #[token("else")]
+                    Keyword,
+                    #[regex("(?&alpha)+!", |_| ())]
+                    Macro,
+                }
+            })
+            .unwrap()
+            .to_string()
+        };
+        // hash maps iterate in a different order in each call, while the order of the subpatterns
+        // must not matter at all
+        let expanded = lexer(false);
+        assert_eq!(lexer(false), expanded);
+        assert_eq!(lexer(true), expanded);
+    }
 }
@@ -1,12 +1,14 @@
 #![forbid(unsafe_code)]
 
+mod cache;
 mod debug;
+mod diagnostics;
 mod generate;
 mod parse;
 
 use generate::generate_impl;
 
-#[proc_macro_derive(Herring, attributes(herring, regex, token))]
+#[proc_macro_derive(Herring, attributes(herring, logos, regex, token))]
 pub fn derive_herring(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
     match generate_impl(tokens.into()) {
         Ok(expanded) => expanded,
@@ -1,13 +1,15 @@
-use herring_automata::Nfa;
+use herring_automata::{Captures, CaseFolding, Nfa, Subpattern};
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use syn::parse::{Parse, ParseStream};
 use syn::{
-    parenthesized, parse2, Error, Expr, ExprClosure, ExprPath, Fields, Ident, ItemEnum, Lit,
-    LitInt, LitStr, Token, Type, Variant,
+    bracketed, parenthesized, parse2, Attribute, Error, Expr, ExprClosure, ExprPath, Fields, Ident,
+    ItemEnum, Lit, LitInt, LitStr, Token, Type, Variant,
 };
 
+use crate::debug::DebugMode;
+use crate::diagnostics::{regex_error, Errors};
 use crate::generate::SKIP_NAME;
 
 fn consume_comma(input: ParseStream) -> bool {
@@ -15,16 +17,27 @@ fn consume_comma(input: ParseStream) -> bool {
         .parse::<Option<Token![,]>>()
         .is_ok_and(|comma| comma.is_some())
 }
-fn peek_ident(input: ParseStream, name: &str) -> bool {
-    input
-        .fork()
-        .parse::<Ident>()
-        .is_ok_and(|ident| ident == name)
+
+/// Returns the key of a named argument of a `token` or `regex` attribute.
+///
+/// The key must be followed by the expected token, so a callback path with the same name is
+/// still accepted as a positional callback.
+fn peek_key(input: ParseStream) -> Option<Ident> {
+    let fork = input.fork();
+    let ident = fork.parse::<Ident>().ok()?;
+    let is_key = match ident.to_string().as_str() {
+        "callback" | "priority" | "and" | "except" | "lookahead" | "name" => fork.peek(Token![=]),
+        "ignore" => fork.peek(syn::token::Paren),
+        "shortest" => fork.is_empty() || fork.peek(Token![,]),
+        _ => false,
+    };
+    is_key.then_some(ident)
 }
 
 struct SubpatternParse {
     name: String,
-    pattern: String,
+    pattern: LitStr,
+    complement: bool,
 }
 impl Parse for SubpatternParse {
     fn parse(input: ParseStream) -> syn::Result<Self> {
@@ -34,8 +47,29 @@ impl Parse for SubpatternParse {
         }
         let name = input.parse::<Ident>()?.to_string();
         let _assign: Token![=] = input.parse()?;
-        let pattern = input.parse::<LitStr>()?.value();
-        Ok(Self { name, pattern })
+        if input.peek(Ident) {
+            let not: Ident = input.parse()?;
+            if not != "not" {
+                return Err(Error::new(
+                    not.span(),
+                    "expected a string or `not(\"regex\")`",
+                ));
+            }
+            let content;
+            parenthesized!(content in input);
+            let pattern = content.parse::<LitStr>()?;
+            return Ok(Self {
+                name,
+                pattern,
+                complement: true,
+            });
+        }
+        let pattern = input.parse::<LitStr>()?;
+        Ok(Self {
+            name,
+            pattern,
+            complement: false,
+        })
     }
 }
 
@@ -75,7 +109,7 @@ impl Parse for PriorityParse {
 }
 
 struct IgnoreParse {
-    case: bool,
+    case_folding: CaseFolding,
 }
 impl Parse for IgnoreParse {
     fn parse(input: ParseStream) -> syn::Result<Self> {
@@ -88,13 +122,13 @@ impl Parse for IgnoreParse {
         let content;
         parenthesized!(content in input);
         let flag: Ident = content.parse()?;
-        if flag == "case" {
-            Ok(Self {
-                case: flag == "case",
-            })
-        } else {
-            Err(Error::new(flag.span(), "unsupported ignore flag"))
-        }
+        let case_folding = match flag.to_string().as_str() {
+            "case" => CaseFolding::Simple,
+            "full_case" => CaseFolding::Full,
+            "ascii_case" => CaseFolding::Ascii,
+            _ => return Err(Error::new(flag.span(), "unsupported ignore flag")),
+        };
+        Ok(Self { case_folding })
     }
 }
 
@@ -110,65 +144,404 @@ fn bytes_to_regex(bytes: &[u8]) -> String {
     res
 }
 
+struct LookaheadParse {
+    regex: String,
+    span: Span,
+    /// String literal containing the lookahead at the offset, in which syntax errors are located.
+    lit: Option<(LitStr, usize)>,
+}
+impl Parse for LookaheadParse {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.fork().parse()?;
+        if ident != "lookahead" {
+            return Err(input.error("expected `lookahead`"));
+        } else {
+            let _ = input.parse::<Ident>();
+        }
+        let _assign: Token![=] = input.parse()?;
+        let lit: LitStr = input.parse()?;
+        Ok(Self {
+            regex: lit.value(),
+            span: lit.span(),
+            lit: Some((lit, 0)),
+        })
+    }
+}
+
+/// Regexes combined with a token by intersection or difference, which are given as a string or
+/// an array of strings matching any of them.
+struct OperandParse {
+    regexes: Vec<String>,
+    span: Span,
+    /// String literals of the regexes, in which syntax errors are located.
+    lits: Vec<LitStr>,
+}
+impl OperandParse {
+    fn parse_named(input: ParseStream, name: &str) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        if ident != name {
+            return Err(Error::new(ident.span(), format!("expected `{name}`")));
+        }
+        let _assign: Token![=] = input.parse()?;
+        let span = input.span();
+        let lits = if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            content
+                .parse_terminated(|input| input.parse::<LitStr>(), Token![,])?
+                .into_iter()
+                .collect()
+        } else {
+            vec![input.parse::<LitStr>()?]
+        };
+        let regexes = lits.iter().map(LitStr::value).collect();
+        Ok(Self {
+            regexes,
+            span,
+            lits,
+        })
+    }
+    fn regex(&self) -> String {
+        self.regexes
+            .iter()
+            .map(|regex| format!("(?:{regex})"))
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+    /// Reports an error of the combined regex in the literal of the regex containing it.
+    fn regex_error(
+        &self,
+        mut err: herring_automata::Error,
+        subpattern_lits: &BTreeMap<String, LitStr>,
+    ) -> Error {
+        let mut lit = None;
+        if let Some(location) = err.location.as_mut().filter(|l| l.subpattern.is_none()) {
+            // each regex is wrapped in `(?:` and `)` and followed by `|`
+            let mut start = 0;
+            for (regex, regex_lit) in self.regexes.iter().zip(&self.lits) {
+                let end = start + regex.len() + 4;
+                if location.range.start < end {
+                    let offset = (start + 3).min(location.range.start);
+                    location.range = location.range.start - offset
+                        ..(location.range.end - offset).min(regex.len());
+                    lit = Some(regex_lit);
+                    break;
+                }
+                start = end + 1;
+            }
+        }
+        regex_error(err, self.span, lit, subpattern_lits)
+    }
+}
+
 struct RegexParse {
     regex: String,
     bytes: Vec<u8>,
     span: Span,
+    /// String literal of the regex, in which syntax errors are located.
+    lit: Option<LitStr>,
     callback: Option<Expr>,
     priority: Option<usize>,
-    ignore_case: bool,
+    case_folding: CaseFolding,
+    shortest: bool,
+    and: Option<OperandParse>,
+    except: Option<OperandParse>,
+    lookahead: Option<LookaheadParse>,
+    /// Name of the token in diagnostics.
+    name: Option<String>,
     binary: bool,
 }
 impl Parse for RegexParse {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let (regex, bytes, span, binary) = match input.parse::<Lit>()? {
-            Lit::Str(lit_str) => (lit_str.value(), Vec::new(), lit_str.span(), false),
+        let (regex, bytes, span, lit, binary) = match input.parse::<Lit>()? {
+            Lit::Str(lit_str) => (
+                lit_str.value(),
+                Vec::new(),
+                lit_str.span(),
+                Some(lit_str),
+                false,
+            ),
             Lit::ByteStr(lit_byte_str) => (
                 bytes_to_regex(&lit_byte_str.value()),
                 lit_byte_str.value(),
                 lit_byte_str.span(),
+                None,
                 true,
             ),
             lit => return Err(Error::new(lit.span(), "expected string or byte string")),
         };
         let mut callback = None;
         let mut priority = None;
-        let mut ignore_case = false;
-        if consume_comma(input) {
-            if peek_ident(input, "priority") {
-                priority = Some(input.parse::<PriorityParse>()?.value);
-                if input.parse::<Option<Token![,]>>()?.is_some() {
-                    ignore_case = input.parse::<IgnoreParse>()?.case;
+        let mut case_folding = CaseFolding::Disabled;
+        let mut shortest = false;
+        let mut and = None;
+        let mut except = None;
+        let mut lookahead = None;
+        let mut name = None;
+        let mut used_keys = HashSet::new();
+        if !input.is_empty() && !input.peek(Token![,]) {
+            return Err(input.error("expected `,` after regex"));
+        }
+        while consume_comma(input) && !input.is_empty() {
+            let Some(key) = peek_key(input) else {
+                // only the callback may be given without a key, directly after the regex
+                let span = input.span();
+                let parsed = input.parse::<FuncRefParse>()?.0;
+                if input.peek(Token![=]) {
+                    return Err(Error::new(
+                        span,
+                        "unknown argument, expected `callback`, `priority`, `ignore`, `shortest`, `and`, `except`, `lookahead` or `name`",
+                    ));
                 }
-            } else if peek_ident(input, "ignore") {
-                ignore_case = input.parse::<IgnoreParse>()?.case;
-            } else {
-                let funcref = input.parse::<FuncRefParse>()?;
-                callback = Some(funcref.0);
-                if consume_comma(input) {
-                    if peek_ident(input, "priority") {
-                        priority = Some(input.parse::<PriorityParse>()?.value);
-                        if consume_comma(input) {
-                            ignore_case = input.parse::<IgnoreParse>()?.case;
-                        }
-                    } else {
-                        ignore_case = input.parse::<IgnoreParse>()?.case;
-                    }
+                if !used_keys.is_empty() {
+                    return Err(Error::new(
+                        span,
+                        "a callback without `callback =` must directly follow the regex",
+                    ));
+                }
+                callback = Some(parsed);
+                used_keys.insert("callback".to_string());
+                continue;
+            };
+            if !used_keys.insert(key.to_string()) {
+                return Err(Error::new(
+                    key.span(),
+                    format!("`{key}` was already specified"),
+                ));
+            }
+            match key.to_string().as_str() {
+                "callback" => {
+                    let _ = input.parse::<Ident>();
+                    let _assign: Token![=] = input.parse()?;
+                    callback = Some(input.parse::<FuncRefParse>()?.0);
+                }
+                "priority" => priority = Some(input.parse::<PriorityParse>()?.value),
+                "ignore" => case_folding = input.parse::<IgnoreParse>()?.case_folding,
+                "shortest" => {
+                    let _ = input.parse::<Ident>();
+                    shortest = true;
+                }
+                "and" => and = Some(OperandParse::parse_named(input, "and")?),
+                "except" => except = Some(OperandParse::parse_named(input, "except")?),
+                "lookahead" => lookahead = Some(input.parse::<LookaheadParse>()?),
+                _ => {
+                    let _ = input.parse::<Ident>();
+                    let _assign: Token![=] = input.parse()?;
+                    name = Some(input.parse::<LitStr>()?.value());
                 }
             }
+            if !input.is_empty() && !input.peek(Token![,]) {
+                return Err(input.error("expected `,` after argument"));
+            }
         }
         Ok(Self {
             regex,
             bytes,
             span,
+            lit,
             callback,
             priority,
-            ignore_case,
+            case_folding,
+            shortest,
+            and,
+            except,
+            lookahead,
+            name,
             binary,
         })
     }
 }
 
+impl RegexParse {
+    /// Creates the token of a `token`, `regex` or `skip` attribute.
+    fn token_attr(&self, token: herring_automata::Token, kind: &str) -> TokenAttr {
+        let is_token = kind == "token";
+        let priority = if let Some(priority) = self.priority {
+            PriorityOrigin::Specified(priority)
+        } else if is_token && self.binary {
+            PriorityOrigin::Bytes(self.bytes.len())
+        } else if is_token {
+            PriorityOrigin::Token(self.regex.clone(), self.case_folding)
+        } else {
+            PriorityOrigin::Regex(self.regex.clone(), self.case_folding, self.binary)
+        };
+        let regex = self.full_regex().escape_debug().to_string();
+        TokenAttr {
+            token,
+            source: if kind == "skip" {
+                format!("skip \"{regex}\"")
+            } else {
+                format!("{kind}(\"{regex}\")")
+            },
+            span: self.span,
+            priority,
+        }
+    }
+    /// Creates the token, which is named in diagnostics, if a name was specified.
+    fn token(&self, nfa: Nfa, priority: usize, value: (String, usize)) -> herring_automata::Token {
+        let token = herring_automata::Token::new(nfa, priority, value);
+        match &self.name {
+            Some(name) => token.with_name(name.clone()),
+            None => token,
+        }
+    }
+    /// Reports an error of the regex at its location in the regex or in a subpattern.
+    fn regex_error(
+        &self,
+        err: herring_automata::Error,
+        subpattern_lits: &BTreeMap<String, LitStr>,
+    ) -> Error {
+        regex_error(err, self.span, self.lit.as_ref(), subpattern_lits)
+    }
+    /// Moves a lookahead at the end of the regex to the `lookahead` property.
+    fn split_lookahead(&mut self) -> syn::Result<()> {
+        let split = Nfa::split_lookahead(&self.regex)
+            .map_err(|err| self.regex_error(err, &BTreeMap::new()))?;
+        if let (regex, Some(lookahead)) = split {
+            if self.lookahead.is_some() {
+                return Err(Error::new(self.span, "lookahead was already specified"));
+            }
+            self.lookahead = Some(LookaheadParse {
+                regex: lookahead.to_string(),
+                span: self.span,
+                // the lookahead follows the regex and `(?=`
+                lit: self.lit.clone().map(|lit| (lit, regex.len() + 3)),
+            });
+            self.regex = regex.to_string();
+        }
+        Ok(())
+    }
+    /// Adds the lookahead as trailing context to the token.
+    fn add_lookahead(
+        &self,
+        token: herring_automata::Token,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        subpattern_lits: &BTreeMap<String, LitStr>,
+    ) -> syn::Result<herring_automata::Token> {
+        let Some(lookahead) = &self.lookahead else {
+            return Ok(token);
+        };
+        let lookahead_error = |mut err: herring_automata::Error| {
+            let (lit, offset) = lookahead
+                .lit
+                .as_ref()
+                .map_or((None, 0), |(lit, offset)| (Some(lit), *offset));
+            if let Some(location) = err.location.as_mut().filter(|l| l.subpattern.is_none()) {
+                location.range = location.range.start + offset..location.range.end + offset;
+            }
+            regex_error(err, lookahead.span, lit, subpattern_lits)
+        };
+        let nfa = match Nfa::from_regex_with_subpatterns(
+            &lookahead.regex,
+            subpatterns,
+            self.case_folding,
+            self.binary,
+        ) {
+            Ok((nfa, _)) => nfa,
+            Err(err) => return Err(lookahead_error(err)),
+        };
+        token.with_lookahead(nfa).map_err(lookahead_error)
+    }
+    /// Intersects the token with the `and` regex and removes the words of the `except` regex.
+    fn apply_operands(
+        &self,
+        nfa: Nfa,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        subpattern_lits: &BTreeMap<String, LitStr>,
+    ) -> syn::Result<Nfa> {
+        let mut nfa = nfa;
+        for (operand, is_and) in [(&self.and, true), (&self.except, false)] {
+            let Some(operand) = operand else {
+                continue;
+            };
+            let other = Nfa::from_regex_with_subpatterns(
+                &operand.regex(),
+                subpatterns,
+                self.case_folding,
+                self.binary,
+            )
+            .map_err(|err| operand.regex_error(err, subpattern_lits))?
+            .0;
+            nfa = if is_and {
+                nfa.intersection(other)
+            } else {
+                nfa.difference(other)
+            }
+            .map_err(|err| operand.regex_error(err, subpattern_lits))?;
+        }
+        Ok(nfa)
+    }
+    /// Restricts the token to the shortest match, if the `shortest` flag is set.
+    fn apply_shortest(&self, nfa: Nfa) -> syn::Result<Nfa> {
+        if !self.shortest {
+            return Ok(nfa);
+        }
+        nfa.into_shortest()
+            .map_err(|err| Error::new(self.span, err.message))
+    }
+    /// Regex used for detecting duplicates, which includes the lookahead.
+    fn full_regex(&self) -> String {
+        let mut regex = self.regex.clone();
+        if let Some(lookahead) = &self.lookahead {
+            regex = format!("{regex}(?={})", lookahead.regex);
+        }
+        if let Some(and) = &self.and {
+            regex = format!("({regex})&({})", and.regex());
+        }
+        if let Some(except) = &self.except {
+            regex = format!("({regex})-({})", except.regex());
+        }
+        if self.shortest {
+            regex.push_str(" (shortest)");
+        }
+        regex
+    }
+}
+
+/// Origin of the priority of a token, which is explained in diagnostics.
+enum PriorityOrigin {
+    Specified(usize),
+    Token(String, CaseFolding),
+    Bytes(usize),
+    Regex(String, CaseFolding, bool),
+}
+
+/// Token of a `token` or `regex` attribute or of a skip regex.
+pub(crate) struct TokenAttr {
+    pub(crate) token: herring_automata::Token,
+    /// Attribute as written, for debug reports.
+    pub(crate) source: String,
+    /// Span of the regex in the attribute.
+    pub(crate) span: Span,
+    priority: PriorityOrigin,
+}
+impl TokenAttr {
+    /// Explains how the priority of the token was derived.
+    pub(crate) fn explain_priority(&self, subpatterns: &BTreeMap<String, Subpattern>) -> String {
+        match &self.priority {
+            PriorityOrigin::Specified(priority) => format!("{priority} as specified"),
+            PriorityOrigin::Token(token, case_folding) => {
+                Nfa::explain_token_priority(token, *case_folding)
+            }
+            PriorityOrigin::Bytes(len) => format!("{} = 2 × {len} bytes", 2 * len),
+            PriorityOrigin::Regex(regex, case_folding, binary) => {
+                Nfa::explain_regex_priority(regex, subpatterns, *case_folding, *binary)
+                    .unwrap_or_else(|err| err.message)
+            }
+        }
+    }
+}
+
+/// Returns the first identifier of the arguments of an attribute.
+fn leading_ident(attr: &Attribute) -> Option<Ident> {
+    attr.parse_args_with(|input: ParseStream| {
+        let ident = input.parse::<Ident>()?;
+        input.parse::<TokenStream>()?;
+        Ok(ident)
+    })
+    .ok()
+}
+
 struct SkipParse(RegexParse);
 
 impl Parse for SkipParse {
@@ -180,6 +553,56 @@ impl Parse for SkipParse {
         Ok(SkipParse(input.parse::<RegexParse>()?))
     }
 }
+impl SkipParse {
+    /// Creates the token of the skip regex with the given number.
+    fn token_attr(
+        mut self,
+        number: usize,
+        subpatterns: &BTreeMap<String, Subpattern>,
+        subpattern_lits: &BTreeMap<String, LitStr>,
+        regex_set: &mut HashSet<(String, CaseFolding, bool)>,
+        callbacks: &mut HashMap<(String, usize), Expr>,
+        binary: &mut bool,
+    ) -> syn::Result<TokenAttr> {
+        let skip = &mut self.0;
+        skip.split_lookahead()?;
+        *binary |= skip.binary;
+        if let Some(callback) = skip.callback.take() {
+            callbacks.insert((SKIP_NAME.to_string(), number), callback);
+        }
+        check_duplicate(
+            "regex",
+            regex_set,
+            &skip.full_regex(),
+            skip.case_folding,
+            skip.binary,
+            skip.span,
+        )?;
+        let (nfa, prio) = match Nfa::from_regex_with_subpatterns(
+            &skip.regex,
+            subpatterns,
+            skip.case_folding,
+            skip.binary,
+        ) {
+            Ok((nfa, prio)) => (nfa, prio),
+            Err(err) => {
+                return Err(skip.regex_error(err, subpattern_lits));
+            }
+        };
+        let nfa = skip.apply_operands(nfa, subpatterns, subpattern_lits)?;
+        let nfa = skip.apply_shortest(nfa)?;
+        let prio = skip.priority.unwrap_or(prio);
+        if nfa.accepts_empty() {
+            return Err(Error::new(skip.span, "skip regex matches empty word"));
+        }
+        let token = skip.add_lookahead(
+            skip.token(nfa, prio, (SKIP_NAME.to_string(), number)),
+            subpatterns,
+            subpattern_lits,
+        )?;
+        Ok(skip.token_attr(token, "skip"))
+    }
+}
 
 pub(crate) struct EnumAttrs {
     pub(crate) extras_ty: Type,
@@ -187,25 +610,60 @@ pub(crate) struct EnumAttrs {
     pub(crate) source_ty: TokenStream,
     pub(crate) ignore_cb: Option<Expr>,
     pub(crate) initial_cb: Option<Expr>,
-    pub(crate) subpatterns: HashMap<String, String>,
+    pub(crate) subpatterns: BTreeMap<String, Subpattern>,
+    /// Literals of the subpatterns, in which syntax errors are located.
+    pub(crate) subpattern_lits: BTreeMap<String, LitStr>,
+    pub(crate) allow_fields: bool,
+    /// Span of the `total` property, which requires that every input can be lexed.
+    pub(crate) total: Option<Span>,
+    /// Maximum number of states of the DFA during the subset construction with the span of the
+    /// limit.
+    pub(crate) max_states: Option<(usize, Span)>,
+    /// Whether the lexer calls its tracer, which is also enabled by the `trace` feature.
+    pub(crate) trace: bool,
+    pub(crate) debug: DebugAttrs,
+}
+
+/// Debug modes enabled by `#[herring(debug(...))]` in addition to `HERRING_DEBUG`.
+#[derive(Default)]
+pub(crate) struct DebugAttrs {
+    pub(crate) modes: BTreeSet<DebugMode>,
+    /// Directory for the debug files relative to the crate.
+    pub(crate) dir: Option<String>,
+}
+
+/// Token strings and regexes, which were already used, for detecting duplicates.
+#[derive(Default)]
+struct UsedRegexes {
+    tokens: HashSet<(String, CaseFolding, bool)>,
+    regexes: HashSet<(String, CaseFolding, bool)>,
 }
+
 fn parse_enum_attrs(
     item: &ItemEnum,
-    tokens: &mut Vec<herring_automata::Token>,
-    regex_set: &mut HashSet<(String, bool, bool)>,
+    tokens: &mut Vec<TokenAttr>,
+    used: &mut UsedRegexes,
     callbacks: &mut HashMap<(String, usize), Expr>,
     binary: &mut bool,
-) -> syn::Result<EnumAttrs> {
+    errors: &mut Errors,
+) -> EnumAttrs {
     let mut extras_ty = Type::Verbatim(quote! {()});
     let mut error_ty = Type::Verbatim(quote! {()});
     let mut source_ty = quote! {};
     let mut ignore_cb: Option<Expr> = None;
     let mut initial_cb: Option<Expr> = None;
-    let mut subpatterns = HashMap::new();
+    let mut subpatterns = BTreeMap::new();
+    let mut subpattern_lits = BTreeMap::new();
+    let mut allow_fields = false;
+    let mut total = None;
+    let mut max_states = None;
+    let mut trace = cfg!(feature = "trace");
+    let mut debug = DebugAttrs::default();
     let mut used_attrs = HashSet::new();
     let mut number = 0;
     for attr in item.attrs.iter() {
-        if attr.path().is_ident("herring") {
+        // `logos` is accepted as an alias for migrating Logos lexers
+        if attr.path().is_ident("herring") || attr.path().is_ident("logos") {
             let property_result = attr.parse_nested_meta(|meta| {
                 if let Some(ident) = meta.path.get_ident() {
                     let name = ident.to_string();
@@ -217,13 +675,50 @@ fn parse_enum_attrs(
                     }
                     match name.as_str() {
                         "extras" => extras_ty = meta.value()?.parse()?,
+                        "error" if meta.input.peek(syn::token::Paren) => {
+                            return Err(Error::new(
+                                ident.span(),
+                                "error callbacks of Logos are not supported (use `error = Type` and implement `Default`)",
+                            ));
+                        }
                         "error" => error_ty = meta.value()?.parse()?,
                         "ignore" => ignore_cb = Some(meta.value()?.parse::<FuncRefParse>()?.0),
                         "initial" => initial_cb = Some(meta.value()?.parse::<FuncRefParse>()?.0),
+                        "allow_fields" => allow_fields = true,
+                        "total" => total = Some(ident.span()),
+                        "trace" => trace = true,
+                        "max_states" => {
+                            let limit: LitInt = meta.value()?.parse()?;
+                            max_states = Some((limit.base10_parse()?, limit.span()));
+                        }
+                        "debug" => meta.parse_nested_meta(|meta| {
+                            let mode = meta.path.get_ident().map(Ident::to_string);
+                            match mode.as_deref() {
+                                Some("dir") => {
+                                    debug.dir = Some(meta.value()?.parse::<LitStr>()?.value());
+                                }
+                                _ => match mode.as_deref().and_then(DebugMode::from_name) {
+                                    Some(mode) => {
+                                        debug.modes.insert(mode);
+                                    }
+                                    None => return Err(meta.error(format!(
+                                        "unknown debug mode, expected `dir` or one of {}",
+                                        DebugMode::names()
+                                    ))),
+                                },
+                            }
+                            Ok(())
+                        })?,
                         "source" => {
                             let ty = meta.value()?.parse::<Type>()?;
                             source_ty = quote! {&'source #ty };
                         }
+                        "crate" | "type" | "export_dir" | "utf8" => {
+                            return Err(Error::new(
+                                ident.span(),
+                                format!("Logos property `{name}` is not supported by Herring"),
+                            ));
+                        }
                         _ => return Err(Error::new(ident.span(), "unexpected property")),
                     }
                     used_attrs.insert(ident.to_string());
@@ -231,70 +726,77 @@ fn parse_enum_attrs(
                 Ok(())
             });
             if let Err(err) = property_result {
-                if let Ok(subpattern) = attr.parse_args::<SubpatternParse>() {
-                    subpatterns.insert(subpattern.name, subpattern.pattern);
-                } else if let Ok(skip) = attr.parse_args::<SkipParse>() {
-                    *binary |= skip.0.binary;
-                    let number = if let Some(callback) = skip.0.callback {
+                // the errors of subpatterns and skip regexes are more precise than the error of
+                // the properties
+                let leading = leading_ident(attr);
+                let leading = leading.as_ref().map(Ident::to_string);
+                if leading.as_deref() == Some("subpattern") {
+                    match attr.parse_args::<SubpatternParse>() {
+                        Ok(subpattern) => {
+                            let regex = subpattern.pattern.value();
+                            let complement = subpattern.complement;
+                            let subpattern_def = Subpattern { regex, complement };
+                            subpatterns.insert(subpattern.name.clone(), subpattern_def);
+                            subpattern_lits.insert(subpattern.name, subpattern.pattern);
+                        }
+                        Err(err) => errors.push(err),
+                    }
+                } else if leading.as_deref() == Some("skip") {
+                    let skip = match attr.parse_args::<SkipParse>() {
+                        Ok(skip) => skip,
+                        Err(err) => {
+                            errors.push(err);
+                            continue;
+                        }
+                    };
+                    let number = if skip.0.callback.is_some() || skip.0.name.is_some() {
                         number += 1;
-                        callbacks.insert((SKIP_NAME.to_string(), number), callback);
                         number
                     } else {
                         0
                     };
-                    check_duplicate(
-                        "regex",
-                        regex_set,
-                        &skip.0.regex,
-                        skip.0.ignore_case,
-                        skip.0.binary,
-                        skip.0.span,
-                    )?;
-                    let (nfa, prio) = match Nfa::from_regex_with_subpatterns(
-                        &skip.0.regex,
+                    match skip.token_attr(
+                        number,
                         &subpatterns,
-                        skip.0.ignore_case,
-                        skip.0.binary,
+                        &subpattern_lits,
+                        &mut used.regexes,
+                        callbacks,
+                        binary,
                     ) {
-                        Ok((nfa, prio)) => (nfa, prio),
-                        Err(err) => {
-                            return Err(Error::new(skip.0.span, err.message));
-                        }
-                    };
-                    let prio = skip.0.priority.unwrap_or(prio);
-                    if nfa.accepts_empty() {
-                        return Err(Error::new(skip.0.span, "skip regex matches empty word"));
+                        Ok(token) => tokens.push(token),
+                        Err(err) => errors.push(err),
                     }
-                    tokens.push(herring_automata::Token::new(
-                        nfa,
-                        prio,
-                        (SKIP_NAME.to_string(), number),
-                    ));
                 } else {
-                    return Err(err);
+                    errors.push(err);
                 }
             }
         }
     }
-    Ok(EnumAttrs {
+    EnumAttrs {
         extras_ty,
         error_ty,
         source_ty,
         ignore_cb,
         initial_cb,
         subpatterns,
-    })
+        subpattern_lits,
+        allow_fields,
+        total,
+        max_states,
+        trace,
+        debug,
+    }
 }
 
 fn check_duplicate(
     kind: &str,
-    set: &mut HashSet<(String, bool, bool)>,
+    set: &mut HashSet<(String, CaseFolding, bool)>,
     value: &str,
-    ignore_case: bool,
+    case_folding: CaseFolding,
     binary: bool,
     span: Span,
 ) -> syn::Result<()> {
-    if set.contains(&(value.to_string(), ignore_case, binary)) {
+    if set.contains(&(value.to_string(), case_folding, binary)) {
         return Err(Error::new(
             span,
             format!(
@@ -304,127 +806,189 @@ fn check_duplicate(
             ),
         ));
     } else {
-        set.insert((value.to_string(), ignore_case, binary));
+        set.insert((value.to_string(), case_folding, binary));
     }
     Ok(())
 }
 
-fn parse_variant_attrs(
+fn parse_variant_attr(
+    attr: &Attribute,
     variant: &Variant,
-    tokens: &mut Vec<herring_automata::Token>,
-    token_set: &mut HashSet<(String, bool, bool)>,
-    regex_set: &mut HashSet<(String, bool, bool)>,
-    callbacks: &mut HashMap<(String, usize), Expr>,
-    subpatterns: &HashMap<String, String>,
+    number: &mut usize,
+    variants: &mut EnumVariants,
+    used: &mut UsedRegexes,
+    attrs: &EnumAttrs,
     binary: &mut bool,
 ) -> syn::Result<()> {
-    let mut number = 0;
-    for attr in variant.attrs.iter() {
-        if let Some(ident) = attr.path().get_ident() {
-            let name = ident.to_string();
-            let tok = variant.ident.to_string();
-            let parse = attr.parse_args::<RegexParse>()?;
-            *binary |= parse.binary;
-            let number = if let Some(callback) = parse.callback {
-                if variant.attrs.len() > 1 {
-                    number += 1;
-                }
-                callbacks.insert((tok.clone(), number), callback);
-                number
+    let Some(ident) = attr.path().get_ident() else {
+        return Ok(());
+    };
+    let subpatterns = &attrs.subpatterns;
+    let name = ident.to_string();
+    let tok = variant.ident.to_string();
+    let mut parse = attr.parse_args::<RegexParse>()?;
+    if parse.callback.is_none() && variants.fields.contains(&tok) {
+        return Err(Error::new(
+            parse.span,
+            "token of a variant with a field requires a callback returning the value",
+        ));
+    }
+    *binary |= parse.binary;
+    let captures = if name == "regex" {
+        parse.split_lookahead()?;
+        Captures::from_regex_with_subpatterns(
+            &parse.regex,
+            subpatterns,
+            parse.case_folding,
+            parse.binary,
+        )
+        .map_err(|err| parse.regex_error(err, &attrs.subpattern_lits))?
+    } else {
+        None
+    };
+    // the outputs of the regexes of a variant are numbered, if they need to be
+    // distinguished by their callback, captures or name
+    let number = if parse.callback.is_some() || captures.is_some() || parse.name.is_some() {
+        if variant.attrs.len() > 1 {
+            *number += 1;
+        }
+        *number
+    } else {
+        0
+    };
+    if let Some(callback) = parse.callback.take() {
+        variants.callbacks.insert((tok.clone(), number), callback);
+    }
+    if let Some(captures) = captures {
+        variants.captures.insert((tok.clone(), number), captures);
+    }
+    let (nfa, prio) = match name.as_str() {
+        "token" => {
+            check_duplicate(
+                &name,
+                &mut used.tokens,
+                &parse.full_regex(),
+                parse.case_folding,
+                parse.binary,
+                parse.span,
+            )?;
+            if parse.binary {
+                (
+                    Nfa::from_bytes(&parse.bytes, parse.case_folding),
+                    parse.bytes.len() * 2,
+                )
             } else {
-                0
-            };
-            let (nfa, prio) = match name.as_str() {
-                "token" => {
-                    check_duplicate(
-                        &name,
-                        token_set,
-                        &parse.regex,
-                        parse.ignore_case,
-                        parse.binary,
-                        parse.span,
-                    )?;
-                    if parse.binary {
-                        (
-                            Nfa::from_bytes(&parse.bytes, parse.ignore_case),
-                            parse.bytes.len() * 2,
-                        )
-                    } else {
-                        Nfa::from_token(&parse.regex, parse.ignore_case)
-                    }
-                }
-                "regex" => {
-                    check_duplicate(
-                        &name,
-                        regex_set,
-                        &parse.regex,
-                        parse.ignore_case,
-                        parse.binary,
-                        parse.span,
-                    )?;
-                    match Nfa::from_regex_with_subpatterns(
-                        &parse.regex,
-                        subpatterns,
-                        parse.ignore_case,
-                        parse.binary,
-                    ) {
-                        Ok((nfa, prio)) => (nfa, prio),
-                        Err(err) => {
-                            return Err(Error::new(parse.span, err.message));
-                        }
-                    }
+                Nfa::from_token(&parse.regex, parse.case_folding)
+            }
+        }
+        "regex" => {
+            check_duplicate(
+                &name,
+                &mut used.regexes,
+                &parse.full_regex(),
+                parse.case_folding,
+                parse.binary,
+                parse.span,
+            )?;
+            match Nfa::from_regex_with_subpatterns(
+                &parse.regex,
+                subpatterns,
+                parse.case_folding,
+                parse.binary,
+            ) {
+                Ok((nfa, prio)) => (nfa, prio),
+                Err(err) => {
+                    return Err(parse.regex_error(err, &attrs.subpattern_lits));
                 }
-                _ => return Err(Error::new(ident.span(), "expected `token` or `regex`")),
-            };
-            let prio = parse.priority.unwrap_or(prio);
-            if nfa.accepts_empty() {
-                return Err(Error::new(parse.span, "token regex matches empty word"));
             }
-            tokens.push(herring_automata::Token::new(nfa, prio, (tok, number)));
         }
+        _ => return Err(Error::new(ident.span(), "expected `token` or `regex`")),
+    };
+    let nfa = parse.apply_operands(nfa, subpatterns, &attrs.subpattern_lits)?;
+    let nfa = parse.apply_shortest(nfa)?;
+    let prio = parse.priority.unwrap_or(prio);
+    if nfa.accepts_empty() {
+        return Err(Error::new(parse.span, "token regex matches empty word"));
     }
+    let token = parse.add_lookahead(
+        parse.token(nfa, prio, (tok, number)),
+        subpatterns,
+        &attrs.subpattern_lits,
+    )?;
+    variants.tokens.push(parse.token_attr(token, &name));
     Ok(())
 }
 
 pub(crate) struct EnumVariants {
-    pub(crate) tokens: Vec<herring_automata::Token>,
+    pub(crate) tokens: Vec<TokenAttr>,
     pub(crate) callbacks: HashMap<(String, usize), Expr>,
+    pub(crate) captures: HashMap<(String, usize), Captures>,
+    /// Variants with a field, which is filled with the value returned by the callback.
+    pub(crate) fields: HashSet<String>,
 }
 fn parse_enum_variants(
     item: &ItemEnum,
-    subpatterns: &HashMap<String, String>,
-    mut tokens: Vec<herring_automata::Token>,
-    regex_set: &mut HashSet<(String, bool, bool)>,
-    mut callbacks: HashMap<(String, usize), Expr>,
+    attrs: &EnumAttrs,
+    tokens: Vec<TokenAttr>,
+    used: &mut UsedRegexes,
+    callbacks: HashMap<(String, usize), Expr>,
     binary: &mut bool,
-) -> syn::Result<EnumVariants> {
-    let mut token_set = HashSet::new();
+    errors: &mut Errors,
+) -> EnumVariants {
+    let mut variants = EnumVariants {
+        tokens,
+        callbacks,
+        captures: HashMap::new(),
+        fields: HashSet::new(),
+    };
     for variant in item.variants.iter() {
         let span = variant.ident.span();
-        match variant.fields {
+        match &variant.fields {
+            Fields::Named(_) | Fields::Unnamed(_) if !attrs.allow_fields => {
+                errors.push(Error::new(
+                    span,
+                    "Herring only supports unit variants (use `#[herring(allow_fields)]` for variants with a field)",
+                ));
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                variants.fields.insert(variant.ident.to_string());
+            }
             Fields::Named(_) | Fields::Unnamed(_) => {
-                return Err(Error::new(span, "Herring only supports unit variants"));
+                errors.push(Error::new(
+                    span,
+                    "Herring only supports variants with a single unnamed field",
+                ));
             }
             Fields::Unit => {}
         }
-        parse_variant_attrs(
-            variant,
-            &mut tokens,
-            &mut token_set,
-            regex_set,
-            &mut callbacks,
-            subpatterns,
-            binary,
-        )?;
+        let mut number = 0;
+        for attr in variant.attrs.iter() {
+            if let Err(err) = parse_variant_attr(
+                attr,
+                variant,
+                &mut number,
+                &mut variants,
+                used,
+                attrs,
+                binary,
+            ) {
+                errors.push(err);
+            }
+        }
     }
-    Ok(EnumVariants { tokens, callbacks })
+    variants
 }
 
 pub(crate) struct Enum {
     pub(crate) name: Ident,
+    /// The enum has a lifetime parameter for borrowing from the source.
+    pub(crate) lifetime: bool,
     pub(crate) attrs: EnumAttrs,
     pub(crate) variants: EnumVariants,
     pub(crate) binary: bool,
+    /// Errors of attributes, which are reported after building the automaton from the other
+    /// attributes.
+    pub(crate) errors: Errors,
 }
 pub(crate) fn parse_enum(tokens: TokenStream) -> syn::Result<Enum> {
     let item = match parse2::<ItemEnum>(tokens) {
@@ -437,29 +1001,45 @@ pub(crate) fn parse_enum(tokens: TokenStream) -> syn::Result<Enum> {
         }
     };
     let name = item.ident.clone();
+    let generics = &item.generics;
+    let lifetime = match (generics.lifetimes().count(), generics.params.len()) {
+        (0, 0) => false,
+        (1, 1) => true,
+        _ => {
+            return Err(Error::new_spanned(
+                generics,
+                "Herring only supports enums with at most one lifetime parameter",
+            ));
+        }
+    };
     let mut tokens = vec![];
-    let mut regex_set = HashSet::new();
+    let mut used = UsedRegexes::default();
     let mut callbacks = HashMap::new();
     let mut binary = false;
+    let mut errors = Errors::default();
     let attrs = parse_enum_attrs(
         &item,
         &mut tokens,
-        &mut regex_set,
+        &mut used,
         &mut callbacks,
         &mut binary,
-    )?;
+        &mut errors,
+    );
     let variants = parse_enum_variants(
         &item,
-        &attrs.subpatterns,
+        &attrs,
         tokens,
-        &mut regex_set,
+        &mut used,
         callbacks,
         &mut binary,
-    )?;
+        &mut errors,
+    );
     Ok(Enum {
         name,
+        lifetime,
         attrs,
         variants,
         binary,
+        errors,
     })
 }
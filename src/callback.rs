@@ -0,0 +1,103 @@
+use super::Herring;
+
+/// Result of a callback, which emits a token, skips the match, or fails.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter<Token, Error> {
+    Emit(Token),
+    Skip,
+    Error(Error),
+}
+
+impl<Token, Error> Filter<Token, Error> {
+    #[inline(always)]
+    fn into_result(self) -> Option<Result<Token, Error>> {
+        match self {
+            Filter::Emit(token) => Some(Ok(token)),
+            Filter::Skip => None,
+            Filter::Error(error) => Some(Err(error)),
+        }
+    }
+}
+
+/// Return type of a token callback, which is converted to the result of the lexer.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a supported return type of a token callback",
+    note = "supported return types are `Result<Token, Error>`, `Option<Token>`, `herring::Filter<Token, Error>` and the Logos forms `bool`, `Option<()>`, `herring::logos::Skip` and `herring::logos::Filter<()>`"
+)]
+pub trait CallbackResult<'source, Token: Herring<'source>> {
+    /// Converts the value returned for a match of the given token, where `None` skips the match.
+    fn into_result(self, token: Token) -> Option<Result<Token, Token::Error>>;
+}
+
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token>
+    for Result<Token, Token::Error>
+{
+    #[inline(always)]
+    fn into_result(self, _token: Token) -> Option<Result<Token, Token::Error>> {
+        Some(self)
+    }
+}
+
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token> for bool {
+    #[inline(always)]
+    fn into_result(self, token: Token) -> Option<Result<Token, Token::Error>> {
+        Some(if self {
+            Ok(token)
+        } else {
+            Err(Default::default())
+        })
+    }
+}
+
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token> for Option<()> {
+    #[inline(always)]
+    fn into_result(self, token: Token) -> Option<Result<Token, Token::Error>> {
+        Some(match self {
+            Some(()) => Ok(token),
+            None => Err(Default::default()),
+        })
+    }
+}
+
+/// A token is reclassified by returning `Some`, while `None` is an error.
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token> for Option<Token> {
+    #[inline(always)]
+    fn into_result(self, _token: Token) -> Option<Result<Token, Token::Error>> {
+        Some(self.ok_or_else(Default::default))
+    }
+}
+
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token>
+    for Filter<Token, Token::Error>
+{
+    #[inline(always)]
+    fn into_result(self, _token: Token) -> Option<Result<Token, Token::Error>> {
+        Filter::into_result(self)
+    }
+}
+
+/// Return type of a skip callback, which is converted to the result of the lexer.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a supported return type of a skip callback",
+    note = "supported return types are `()` and `herring::Filter<Token, Error>`"
+)]
+pub trait SkipCallbackResult<'source, Token: Herring<'source>> {
+    /// Converts the value returned for a skipped match, where `None` skips the match.
+    fn into_result(self) -> Option<Result<Token, Token::Error>>;
+}
+
+impl<'source, Token: Herring<'source>> SkipCallbackResult<'source, Token> for () {
+    #[inline(always)]
+    fn into_result(self) -> Option<Result<Token, Token::Error>> {
+        None
+    }
+}
+
+impl<'source, Token: Herring<'source>> SkipCallbackResult<'source, Token>
+    for Filter<Token, Token::Error>
+{
+    #[inline(always)]
+    fn into_result(self) -> Option<Result<Token, Token::Error>> {
+        Filter::into_result(self)
+    }
+}
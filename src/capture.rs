@@ -0,0 +1,147 @@
+use super::Span;
+
+/// Instruction of a capture program generated for a token regex with named capture groups.
+#[derive(Debug)]
+pub enum CaptureInst {
+    /// Consume a byte in one of the ranges and continue at the given instruction.
+    Ranges(&'static [(u8, u8)], usize),
+    /// Continue at both instructions, preferring the first one.
+    Split(usize, usize),
+    Jump(usize),
+    /// Store the current offset in the given register.
+    Save(usize),
+    Match,
+}
+
+/// Program for determining the spans of the named capture groups of a token.
+#[derive(Debug)]
+pub struct Captures {
+    pub names: &'static [&'static str],
+    pub insts: &'static [CaptureInst],
+}
+
+/// Buffers of the Pike VM, which are reused for all tokens of a lexer.
+#[derive(Debug, Default)]
+pub struct CaptureScratch {
+    /// Instructions of the threads before and after the current byte in priority order.
+    current: Vec<usize>,
+    next: Vec<usize>,
+    /// Registers of the threads, `2 * names.len()` for each instruction.
+    current_registers: Vec<Option<usize>>,
+    next_registers: Vec<Option<usize>>,
+    visited: Vec<bool>,
+    /// Registers of the thread, which is added.
+    registers: Vec<Option<usize>>,
+    stack: Vec<Frame>,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Explore(usize),
+    /// Restores a register overwritten by a `Save` after its successors were explored.
+    Restore(usize, Option<usize>),
+}
+
+impl Captures {
+    /// Adds the thread with the registers in `scratch.registers` and all threads reachable
+    /// without consuming a byte to the list.
+    fn add_thread(&self, scratch: &mut CaptureScratch, to_next: bool, pc: usize, offset: usize) {
+        let count = 2 * self.names.len();
+        let (list, list_registers) = if to_next {
+            (&mut scratch.next, &mut scratch.next_registers)
+        } else {
+            (&mut scratch.current, &mut scratch.current_registers)
+        };
+        scratch.stack.push(Frame::Explore(pc));
+        while let Some(frame) = scratch.stack.pop() {
+            let pc = match frame {
+                Frame::Explore(pc) => pc,
+                Frame::Restore(register, value) => {
+                    scratch.registers[register] = value;
+                    continue;
+                }
+            };
+            if std::mem::replace(&mut scratch.visited[pc], true) {
+                continue;
+            }
+            match self.insts[pc] {
+                CaptureInst::Split(first, second) => {
+                    scratch.stack.push(Frame::Explore(second));
+                    scratch.stack.push(Frame::Explore(first));
+                }
+                CaptureInst::Jump(next) => scratch.stack.push(Frame::Explore(next)),
+                CaptureInst::Save(register) => {
+                    let value = scratch.registers[register].replace(offset);
+                    scratch.stack.push(Frame::Restore(register, value));
+                    scratch.stack.push(Frame::Explore(pc + 1));
+                }
+                CaptureInst::Ranges(..) | CaptureInst::Match => {
+                    list.push(pc);
+                    list_registers[pc * count..(pc + 1) * count]
+                        .copy_from_slice(&scratch.registers);
+                }
+            }
+        }
+    }
+
+    /// Runs a Pike VM on the token, which must be matched entirely.
+    ///
+    /// Replaces `spans` with the spans of the named capture groups, which participated in the
+    /// match.
+    pub fn run(
+        &self,
+        scratch: &mut CaptureScratch,
+        start: usize,
+        mut bytes: impl Iterator<Item = u8>,
+        spans: &mut Vec<(&'static str, Span)>,
+    ) {
+        spans.clear();
+        let count = 2 * self.names.len();
+        scratch.current.clear();
+        scratch.next.clear();
+        scratch.current_registers.clear();
+        scratch
+            .current_registers
+            .resize(self.insts.len() * count, None);
+        scratch.next_registers.clear();
+        scratch
+            .next_registers
+            .resize(self.insts.len() * count, None);
+        scratch.visited.clear();
+        scratch.visited.resize(self.insts.len(), false);
+        scratch.registers.clear();
+        scratch.registers.resize(count, None);
+        self.add_thread(scratch, false, 0, start);
+        let mut offset = start;
+        loop {
+            let b = bytes.next();
+            scratch.visited.fill(false);
+            for i in 0..scratch.current.len() {
+                let pc = scratch.current[i];
+                let registers = &scratch.current_registers[pc * count..(pc + 1) * count];
+                match (&self.insts[pc], b) {
+                    (CaptureInst::Match, None) => {
+                        spans.extend(self.names.iter().enumerate().filter_map(|(i, name)| {
+                            Some((*name, registers[2 * i]?..registers[2 * i + 1]?))
+                        }));
+                        return;
+                    }
+                    (CaptureInst::Ranges(ranges, to), Some(b))
+                        if ranges.iter().any(|(start, end)| *start <= b && b <= *end) =>
+                    {
+                        scratch.registers.copy_from_slice(registers);
+                        self.add_thread(scratch, true, *to, offset + 1);
+                    }
+                    _ => {}
+                }
+            }
+            if b.is_none() || scratch.next.is_empty() {
+                return;
+            }
+            std::mem::swap(&mut scratch.current, &mut scratch.next);
+            std::mem::swap(&mut scratch.current_registers, &mut scratch.next_registers);
+            scratch.next.clear();
+            offset += 1;
+        }
+    }
+}
@@ -1,9 +1,16 @@
 #![forbid(unsafe_code)]
 
 mod assert;
+mod callback;
+mod capture;
+pub mod logos;
+mod trace;
 
 pub use assert::assert_lex;
+pub use callback::{CallbackResult, Filter, SkipCallbackResult};
+pub use capture::{CaptureInst, CaptureScratch, Captures};
 pub use herring_derive::Herring;
+pub use trace::{PrettyTrace, Trace};
 
 pub type Span = core::ops::Range<usize>;
 
@@ -77,6 +84,9 @@ pub struct Lexer<'source, Token: Herring<'source>> {
     pub offset: usize,
     pub source: Token::Source,
     pub extras: Token::Extras,
+    captures: Vec<(&'static str, Span)>,
+    capture_scratch: CaptureScratch,
+    tracer: Option<Box<dyn Trace + Send + 'source>>,
 }
 
 impl<'source, Token: Herring<'source>> Lexer<'source, Token> {
@@ -89,6 +99,9 @@ impl<'source, Token: Herring<'source>> Lexer<'source, Token> {
             offset: 0,
             source,
             extras: Default::default(),
+            captures: Vec::new(),
+            capture_scratch: CaptureScratch::default(),
+            tracer: None,
         }
     }
     pub fn with_extras(source: Token::Source, extras: Token::Extras) -> Self {
@@ -97,6 +110,9 @@ impl<'source, Token: Herring<'source>> Lexer<'source, Token> {
             offset: 0,
             source,
             extras,
+            captures: Vec::new(),
+            capture_scratch: CaptureScratch::default(),
+            tracer: None,
         }
     }
     #[inline(always)]
@@ -106,6 +122,16 @@ impl<'source, Token: Herring<'source>> Lexer<'source, Token> {
         self.offset += 1;
         self.source.get_byte(offset)
     }
+    /// Reads the next byte like `next_byte` and reports it to the tracer, which is called by
+    /// lexers with tracing.
+    #[inline(always)]
+    pub fn next_byte_traced(&mut self) -> Option<u8> {
+        let byte = self.next_byte();
+        if let (Some(byte), Some(tracer)) = (byte, self.tracer.as_mut()) {
+            tracer.byte_consumed(byte, self.offset - 1);
+        }
+        byte
+    }
     #[inline(always)]
     pub fn bump(&mut self, n: usize) {
         self.offset += n;
@@ -122,6 +148,42 @@ impl<'source, Token: Herring<'source>> Lexer<'source, Token> {
     pub fn span(&self) -> Span {
         self.start..self.offset
     }
+    /// Returns the span of a named capture group of the current token.
+    pub fn capture(&self, name: &str) -> Option<Span> {
+        self.captures
+            .iter()
+            .find(|(capture, _)| *capture == name)
+            .map(|(_, span)| span.clone())
+    }
+    /// Determines the spans of the capture groups of the current token, which is called by the
+    /// generated lexer.
+    pub fn set_captures(&mut self, captures: &'static Captures) {
+        let bytes = (self.start..self.offset).map_while(|offset| self.source.get_byte(offset));
+        captures.run(
+            &mut self.capture_scratch,
+            self.start,
+            bytes,
+            &mut self.captures,
+        );
+    }
+    #[inline(always)]
+    pub fn clear_captures(&mut self) {
+        self.captures.clear();
+    }
+    /// Sets the tracer, which receives the decisions of lexers with tracing.
+    ///
+    /// The tracer must be `Send`, so the lexer can still be moved to other threads.
+    pub fn set_tracer(&mut self, tracer: impl Trace + Send + 'source) {
+        self.tracer = Some(Box::new(tracer));
+    }
+    /// Removes the tracer and returns it.
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Trace + Send + 'source>> {
+        self.tracer.take()
+    }
+    #[inline(always)]
+    pub fn tracer(&mut self) -> Option<&mut (dyn Trace + Send + 'source)> {
+        self.tracer.as_deref_mut()
+    }
     #[inline(always)]
     pub fn spanned(self) -> SpannedIter<'source, Token> {
         SpannedIter { lexer: self }
@@ -141,6 +203,13 @@ pub struct SpannedIter<'source, Token: Herring<'source>> {
     lexer: Lexer<'source, Token>,
 }
 
+impl<'source, Token: Herring<'source>> SpannedIter<'source, Token> {
+    /// Returns the span of a named capture group of the last token.
+    pub fn capture(&self, name: &str) -> Option<Span> {
+        self.lexer.capture(name)
+    }
+}
+
 impl<'source, Token: Herring<'source>> Iterator for SpannedIter<'source, Token> {
     type Item = (Result<Token, Token::Error>, Span);
 
@@ -0,0 +1,34 @@
+//! Callback return types of Logos, which are accepted for migrating a Logos lexer.
+
+use super::{CallbackResult, Herring, Lexer};
+
+/// Skips the match of the token.
+pub struct Skip;
+
+/// Emits the token or skips the match.
+pub enum Filter<T> {
+    Accept(T),
+    Skip,
+}
+
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token> for Skip {
+    #[inline(always)]
+    fn into_result(self, _token: Token) -> Option<Result<Token, Token::Error>> {
+        None
+    }
+}
+
+impl<'source, Token: Herring<'source>> CallbackResult<'source, Token> for Filter<()> {
+    #[inline(always)]
+    fn into_result(self, token: Token) -> Option<Result<Token, Token::Error>> {
+        match self {
+            Filter::Accept(()) => Some(Ok(token)),
+            Filter::Skip => None,
+        }
+    }
+}
+
+/// Callback, which skips the match of the token (e.g. `#[regex(r"[ \t]+", herring::logos::skip)]`).
+pub fn skip<'source, Token: Herring<'source>>(_lexer: &mut Lexer<'source, Token>) -> Skip {
+    Skip
+}
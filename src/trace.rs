@@ -0,0 +1,80 @@
+use super::Span;
+use std::io::Write;
+
+/// Receives the decisions of a lexer, which is generated with `#[herring(trace)]` or the `trace`
+/// feature, while it lexes its input.
+///
+/// All offsets are byte offsets into the source. Tokens are identified by the name of their
+/// variant, and skipped regexes by `skipped regex`.
+pub trait Trace {
+    /// The lexer enters a state of its automaton before reading the byte at `offset`.
+    fn state_enter(&mut self, _state: usize, _offset: usize) {}
+    /// The lexer reads the byte at `offset`, which may be put back if no transition matches it.
+    fn byte_consumed(&mut self, _byte: u8, _offset: usize) {}
+    /// The input up to `end` matches the token, which is emitted unless a longer match is found.
+    fn accept_recorded(&mut self, _token: &str, _end: usize) {}
+    /// The lexer returns from the end of the longest attempted match to the last accepted match.
+    fn backtrack(&mut self, _from: usize, _to: usize) {}
+    /// The lexer returns a token or, if the input matches no token, an error.
+    fn token_emitted(&mut self, _token: Option<&str>, _span: Span) {}
+}
+
+/// Tracer, which pretty-prints the decisions of a lexer to stderr or another writer.
+///
+/// For the input `if` it prints
+/// ```text
+/// S0 @0
+///   'i' @0
+/// S4 @1
+///   accept `Ident` until 1
+///   'f' @1
+/// S5 @2
+///   accept `If` until 2
+/// emit `If` 0..2
+/// ```
+pub struct PrettyTrace<W: Write = std::io::Stderr> {
+    writer: W,
+}
+
+impl PrettyTrace {
+    pub fn new() -> Self {
+        Self::with_writer(std::io::stderr())
+    }
+}
+
+impl Default for PrettyTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> PrettyTrace<W> {
+    pub fn with_writer(writer: W) -> Self {
+        Self { writer }
+    }
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+// errors of the writer are ignored, as tracing must not change the result of the lexer
+impl<W: Write> Trace for PrettyTrace<W> {
+    fn state_enter(&mut self, state: usize, offset: usize) {
+        let _ = writeln!(self.writer, "S{state} @{offset}");
+    }
+    fn byte_consumed(&mut self, byte: u8, offset: usize) {
+        let _ = writeln!(self.writer, "  '{}' @{offset}", byte.escape_ascii());
+    }
+    fn accept_recorded(&mut self, token: &str, end: usize) {
+        let _ = writeln!(self.writer, "  accept `{token}` until {end}");
+    }
+    fn backtrack(&mut self, from: usize, to: usize) {
+        let _ = writeln!(self.writer, "  backtrack from {from} to {to}");
+    }
+    fn token_emitted(&mut self, token: Option<&str>, span: Span) {
+        let _ = match token {
+            Some(token) => writeln!(self.writer, "emit `{token}` {span:?}"),
+            None => writeln!(self.writer, "error {span:?}"),
+        };
+    }
+}
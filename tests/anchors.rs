@@ -0,0 +1,62 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip "[ \n]+")]
+enum Preprocessor {
+    #[regex("^#[a-z]+")]
+    Directive,
+    #[token("#")]
+    Hash,
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("[a-z]+;$")]
+    Statement,
+}
+
+#[test]
+fn test_line_anchors() {
+    assert_lex(
+        "#define x #y\n#include\nfoo;\nbar; baz",
+        &[
+            (Ok(Preprocessor::Directive), "#define", 0..7),
+            (Ok(Preprocessor::Ident), "x", 8..9),
+            (Ok(Preprocessor::Hash), "#", 10..11),
+            (Ok(Preprocessor::Ident), "y", 11..12),
+            (Ok(Preprocessor::Directive), "#include", 13..21),
+            (Ok(Preprocessor::Statement), "foo;", 22..26),
+            (Ok(Preprocessor::Ident), "bar", 27..30),
+            (Err(()), ";", 30..31),
+            (Ok(Preprocessor::Ident), "baz", 32..35),
+        ],
+    );
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Legacy {
+    #[regex(r"\bif\b")]
+    If,
+    #[regex(r"[a-z]+", priority = 1)]
+    Ident,
+    #[regex(r"[a-z]+\B[0-9]")]
+    Numbered,
+    #[token("-")]
+    Minus,
+}
+
+#[test]
+fn test_word_boundaries() {
+    assert_lex(
+        "if iffy if-x -if ab1",
+        &[
+            (Ok(Legacy::If), "if", 0..2),
+            (Ok(Legacy::Ident), "iffy", 3..7),
+            (Ok(Legacy::If), "if", 8..10),
+            (Ok(Legacy::Minus), "-", 10..11),
+            (Ok(Legacy::Ident), "x", 11..12),
+            (Ok(Legacy::Minus), "-", 13..14),
+            (Ok(Legacy::If), "if", 14..16),
+            (Ok(Legacy::Numbered), "ab1", 17..20),
+        ],
+    );
+}
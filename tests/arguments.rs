@@ -0,0 +1,46 @@
+use herring::{assert_lex, Herring, Lexer};
+
+fn number(lexer: &mut Lexer<Token>) -> Result<Token, ()> {
+    if lexer.slice().len() > 3 {
+        Err(())
+    } else {
+        Ok(Token::Number)
+    }
+}
+
+fn name(_lexer: &mut Lexer<Token>) -> Result<Token, ()> {
+    Ok(Token::Ident)
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +", name = "whitespace")]
+enum Token {
+    #[regex("[0-9]+", priority = 3, callback = number)]
+    Number,
+    #[token("if", ignore(case), name = "if keyword")]
+    If,
+    // `name` is a positional callback, as it is not followed by `=`
+    #[regex("[a-z]+", name, priority = 1, ignore(ascii_case))]
+    Ident,
+    #[regex("[a-z]+[0-9]+", name = "versioned identifier", callback = |_| Ok(Token::Versioned))]
+    Versioned,
+    #[regex("<.*>", shortest, name = "tag")]
+    Tag,
+}
+
+#[test]
+fn test_named_arguments() {
+    assert_lex(
+        "IF iF foo 123 1234 abc12 <a> <b>",
+        &[
+            (Ok(Token::If), "IF", 0..2),
+            (Ok(Token::If), "iF", 3..5),
+            (Ok(Token::Ident), "foo", 6..9),
+            (Ok(Token::Number), "123", 10..13),
+            (Err(()), "1234", 14..18),
+            (Ok(Token::Versioned), "abc12", 19..24),
+            (Ok(Token::Tag), "<a>", 25..28),
+            (Ok(Token::Tag), "<b>", 29..32),
+        ],
+    );
+}
@@ -0,0 +1,75 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Sql {
+    #[token("select", ignore(ascii_case))]
+    Select,
+    #[token("äpfel", ignore(ascii_case))]
+    Apfel,
+    #[regex("[a-zé]+_id", ignore(ascii_case))]
+    Id,
+    #[regex(r"(?<name>[a-z]+)\(\)", ignore(ascii_case))]
+    Call,
+    #[regex("[a-zäéÄÉ]+")]
+    Ident,
+}
+
+#[test]
+fn test_ascii_case() {
+    assert_lex(
+        "select SELECT SeLeCt äpfel äPFEL Äpfel",
+        &[
+            (Ok(Sql::Select), "select", 0..6),
+            (Ok(Sql::Select), "SELECT", 7..13),
+            (Ok(Sql::Select), "SeLeCt", 14..20),
+            (Ok(Sql::Apfel), "äpfel", 21..27),
+            (Ok(Sql::Apfel), "äPFEL", 28..34),
+            (Ok(Sql::Ident), "Äpfel", 35..41),
+        ],
+    );
+}
+
+#[test]
+fn test_ascii_case_regex() {
+    assert_lex(
+        "abc_id ABC_ID é_ID É_id",
+        &[
+            (Ok(Sql::Id), "abc_id", 0..6),
+            (Ok(Sql::Id), "ABC_ID", 7..13),
+            (Ok(Sql::Id), "é_ID", 14..19),
+            (Ok(Sql::Ident), "É", 20..22),
+            (Err(()), "_", 22..23),
+            (Ok(Sql::Ident), "id", 23..25),
+        ],
+    );
+}
+
+#[test]
+fn test_ascii_case_capture() {
+    let mut lex = Sql::lexer("FOO()").spanned();
+    assert_eq!(lex.next(), Some((Ok(Sql::Call), 0..5)));
+    assert_eq!(lex.capture("name"), Some(0..3));
+    assert_eq!(lex.next(), None);
+}
+
+#[derive(Herring, Debug, PartialEq)]
+enum Binary {
+    #[token(b"\xFFend", ignore(ascii_case))]
+    End,
+    #[regex(b"[\xC0-\xCFa]+", ignore(ascii_case))]
+    Data,
+}
+
+#[test]
+fn test_ascii_case_bytes() {
+    assert_lex(
+        &b"\xFFEND\xFFend\xC1A\xC1\xE1"[..],
+        &[
+            (Ok(Binary::End), b"\xFFEND", 0..4),
+            (Ok(Binary::End), b"\xFFend", 4..8),
+            (Ok(Binary::Data), b"\xC1A\xC1", 8..11),
+            (Err(()), b"\xE1", 11..12),
+        ],
+    );
+}
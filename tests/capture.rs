@@ -0,0 +1,72 @@
+use herring::{Herring, Lexer};
+
+fn exponent(lexer: &mut Lexer<Token>) -> Result<Token, ()> {
+    match lexer.capture("exponent") {
+        Some(span) if &lexer.source[span.clone()] == "0" => Err(()),
+        _ => Ok(Token::Float),
+    }
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Token {
+    #[regex(r"(?<digits>\d+)(\.(?<fraction>\d+))?e(?<exponent>\d+)", exponent)]
+    #[regex(r"(?<digits>[0-9]+)\.(?<fraction>[0-9]+)")]
+    Float,
+    #[regex(r"\$(?<name>[a-z]+)")]
+    Variable,
+    #[regex(r#"r(?<tag>#*)"(?<content>[^"]*)"(?<end>#*)"#)]
+    RawString,
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("#(?<byte>[0-9a-f]{2}){3}")]
+    Color,
+}
+
+#[test]
+fn test_captures() {
+    let mut lex = Token::lexer("1.5e10 $foo 42.0 bar").spanned();
+    assert_eq!(lex.next(), Some((Ok(Token::Float), 0..6)));
+    assert_eq!(lex.capture("digits"), Some(0..1));
+    assert_eq!(lex.capture("fraction"), Some(2..3));
+    assert_eq!(lex.capture("exponent"), Some(4..6));
+    assert_eq!(lex.next(), Some((Ok(Token::Variable), 7..11)));
+    assert_eq!(lex.capture("name"), Some(8..11));
+    assert_eq!(lex.capture("digits"), None);
+    assert_eq!(lex.next(), Some((Ok(Token::Float), 12..16)));
+    assert_eq!(lex.capture("fraction"), Some(15..16));
+    assert_eq!(lex.capture("exponent"), None);
+    assert_eq!(lex.next(), Some((Ok(Token::Ident), 17..20)));
+    assert_eq!(lex.capture("name"), None);
+    assert_eq!(lex.next(), None);
+}
+
+#[test]
+fn test_raw_string() {
+    let mut lex = Token::lexer(r###"r##"a b"## r"""###).spanned();
+    assert_eq!(lex.next(), Some((Ok(Token::RawString), 0..10)));
+    assert_eq!(lex.capture("tag"), Some(1..3));
+    assert_eq!(lex.capture("content"), Some(4..7));
+    assert_eq!(lex.capture("end"), Some(8..10));
+    assert_eq!(lex.next(), Some((Ok(Token::RawString), 11..14)));
+    assert_eq!(lex.capture("tag"), Some(12..12));
+    assert_eq!(lex.capture("content"), Some(13..13));
+    assert_eq!(lex.next(), None);
+}
+
+#[test]
+fn test_callback_captures() {
+    let mut lex = Token::lexer("12e0 1e2");
+    assert_eq!(lex.next(), Some(Err(())));
+    assert_eq!(lex.capture("digits"), Some(0..2));
+    assert_eq!(lex.next(), Some(Ok(Token::Float)));
+    assert_eq!(lex.capture("exponent"), Some(7..8));
+}
+
+#[test]
+fn test_repeated_group() {
+    let mut lex = Token::lexer("#12ab00").spanned();
+    assert_eq!(lex.next(), Some((Ok(Token::Color), 0..7)));
+    assert_eq!(lex.capture("byte"), Some(5..7));
+    assert_eq!(lex.next(), None);
+}
@@ -0,0 +1,53 @@
+use herring::{Herring, Lexer};
+use std::num::IntErrorKind;
+
+#[derive(Default, Debug, Clone, PartialEq)]
+enum LexingError {
+    Overflow,
+    #[default]
+    Invalid,
+}
+
+fn int<'s>(lexer: &mut Lexer<'s, Token<'s>>) -> Result<i64, LexingError> {
+    lexer
+        .slice()
+        .parse()
+        .map_err(|err: std::num::ParseIntError| {
+            if *err.kind() == IntErrorKind::PosOverflow {
+                LexingError::Overflow
+            } else {
+                LexingError::Invalid
+            }
+        })
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(allow_fields)]
+#[herring(error = LexingError)]
+#[herring(skip " +")]
+enum Token<'s> {
+    #[regex("[0-9]+", int)]
+    Int(i64),
+    #[regex("[a-z]+", |lexer| Ok(lexer.slice()))]
+    Ident(&'s str),
+    #[regex("'[^']'", |lexer| Ok(lexer.slice().chars().nth(1).unwrap()))]
+    #[token("'''", |_| Ok('\''))]
+    Char(char),
+    #[token("+")]
+    Plus,
+}
+
+#[test]
+fn test_fields() {
+    let mut lex = Token::lexer("x + 42 + 'a' + ''' + 99999999999999999999").spanned();
+    assert_eq!(lex.next(), Some((Ok(Token::Ident("x")), 0..1)));
+    assert_eq!(lex.next(), Some((Ok(Token::Plus), 2..3)));
+    assert_eq!(lex.next(), Some((Ok(Token::Int(42)), 4..6)));
+    assert_eq!(lex.next(), Some((Ok(Token::Plus), 7..8)));
+    assert_eq!(lex.next(), Some((Ok(Token::Char('a')), 9..12)));
+    assert_eq!(lex.next(), Some((Ok(Token::Plus), 13..14)));
+    assert_eq!(lex.next(), Some((Ok(Token::Char('\'')), 15..18)));
+    assert_eq!(lex.next(), Some((Ok(Token::Plus), 19..20)));
+    assert_eq!(lex.next(), Some((Err(LexingError::Overflow), 21..41)));
+    assert_eq!(lex.next(), None);
+}
@@ -0,0 +1,91 @@
+use herring::{Filter, Herring, Lexer};
+
+#[derive(Default)]
+struct Extras {
+    depth: usize,
+}
+
+fn open(lexer: &mut Lexer<Token>) -> Filter<Token, String> {
+    lexer.extras.depth += 1;
+    if lexer.extras.depth > 2 {
+        Filter::Error(format!("nesting too deep at {}", lexer.start))
+    } else {
+        Filter::Emit(Token::Open)
+    }
+}
+
+fn close(lexer: &mut Lexer<Token>) -> Filter<Token, String> {
+    match lexer.extras.depth.checked_sub(1) {
+        Some(depth) => {
+            lexer.extras.depth = depth;
+            Filter::Emit(Token::Close)
+        }
+        None => Filter::Skip,
+    }
+}
+
+fn keyword(lexer: &mut Lexer<Token>) -> Option<Token> {
+    match lexer.slice() {
+        "let" => Some(Token::Let),
+        "fn" => Some(Token::Fn),
+        "_" => None,
+        _ => Some(Token::Ident),
+    }
+}
+
+fn newline(lexer: &mut Lexer<Token>) -> Filter<Token, String> {
+    if lexer.extras.depth > 0 {
+        Filter::Emit(Token::Newline)
+    } else {
+        Filter::Skip
+    }
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(extras = Extras)]
+#[herring(error = String)]
+#[herring(skip " +")]
+#[herring(skip "\n", newline)]
+enum Token {
+    #[token("(", open)]
+    Open,
+    #[token(")", close)]
+    Close,
+    #[regex("[a-z_]+", keyword)]
+    Ident,
+    Let,
+    Fn,
+    Newline,
+}
+
+#[test]
+fn test_filter() {
+    let mut lex = Token::lexer("( ( ( ) ) ) )");
+    assert_eq!(lex.next(), Some(Ok(Token::Open)));
+    assert_eq!(lex.next(), Some(Ok(Token::Open)));
+    assert_eq!(lex.next(), Some(Err("nesting too deep at 4".to_string())));
+    assert_eq!(lex.next(), Some(Ok(Token::Close)));
+    assert_eq!(lex.next(), Some(Ok(Token::Close)));
+    assert_eq!(lex.next(), Some(Ok(Token::Close)));
+    assert_eq!(lex.next(), None);
+}
+
+#[test]
+fn test_option() {
+    let mut lex = Token::lexer("let x fn _");
+    assert_eq!(lex.next(), Some(Ok(Token::Let)));
+    assert_eq!(lex.next(), Some(Ok(Token::Ident)));
+    assert_eq!(lex.next(), Some(Ok(Token::Fn)));
+    assert_eq!(lex.next(), Some(Err(String::new())));
+    assert_eq!(lex.next(), None);
+}
+
+#[test]
+fn test_skip_filter() {
+    let mut lex = Token::lexer("x\n(\n)\n").spanned();
+    assert_eq!(lex.next(), Some((Ok(Token::Ident), 0..1)));
+    assert_eq!(lex.next(), Some((Ok(Token::Open), 2..3)));
+    assert_eq!(lex.next(), Some((Ok(Token::Newline), 3..4)));
+    assert_eq!(lex.next(), Some((Ok(Token::Close), 4..5)));
+    assert_eq!(lex.next(), None);
+}
@@ -0,0 +1,54 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Sql {
+    #[token("straße", ignore(full_case))]
+    Strasse,
+    #[token("ǆ", ignore(full_case))]
+    Dz,
+    #[regex("ﬀ[0-9]+", ignore(full_case))]
+    Ff,
+    #[regex("#(?<tag>ß+)", ignore(full_case))]
+    Tag,
+    #[regex("[a-z]+")]
+    Ident,
+}
+
+#[test]
+fn test_full_case() {
+    assert_lex(
+        "straße STRASSE Strasse strasse STRAẞE Ǆ ǅ ǆ",
+        &[
+            (Ok(Sql::Strasse), "straße", 0..7),
+            (Ok(Sql::Strasse), "STRASSE", 8..15),
+            (Ok(Sql::Strasse), "Strasse", 16..23),
+            (Ok(Sql::Strasse), "strasse", 24..31),
+            (Ok(Sql::Strasse), "STRAẞE", 32..40),
+            (Ok(Sql::Dz), "Ǆ", 41..43),
+            (Ok(Sql::Dz), "ǅ", 44..46),
+            (Ok(Sql::Dz), "ǆ", 47..49),
+        ],
+    );
+}
+
+#[test]
+fn test_full_case_regex() {
+    assert_lex(
+        "ﬀ1 FF23 fF4 ff",
+        &[
+            (Ok(Sql::Ff), "ﬀ1", 0..4),
+            (Ok(Sql::Ff), "FF23", 5..9),
+            (Ok(Sql::Ff), "fF4", 10..13),
+            (Ok(Sql::Ident), "ff", 14..16),
+        ],
+    );
+}
+
+#[test]
+fn test_full_case_capture() {
+    let mut lex = Sql::lexer("#SSßsS").spanned();
+    assert_eq!(lex.next(), Some((Ok(Sql::Tag), 0..7)));
+    assert_eq!(lex.capture("tag"), Some(1..7));
+    assert_eq!(lex.next(), None);
+}
@@ -55,6 +55,7 @@ mod numbers {
 
     #[derive(Herring, Debug, Clone, Copy, PartialEq)]
     #[herring(skip r"[ \t\n\f]+")]
+    #[allow(clippy::enum_variant_names)]
     enum Token {
         #[regex(r"[0-9][0-9_]*")]
         LiteralUnsignedNumber,
@@ -448,8 +449,6 @@ mod maybe_in_loop {
 }
 
 mod unicode_error_split {
-    use super::*;
-
     #[test]
     fn test() {
         use herring::Herring;
@@ -0,0 +1,85 @@
+use herring::logos::{Filter, Skip};
+use herring::{assert_lex, Herring, Lexer};
+
+#[derive(Default)]
+struct Extras {
+    comments: usize,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+enum LexingError {
+    #[default]
+    Invalid,
+}
+
+fn even(lex: &mut Lexer<Token>) -> bool {
+    lex.slice().parse::<u64>().is_ok_and(|n| n % 2 == 0)
+}
+
+fn short(lex: &mut Lexer<Token>) -> Option<()> {
+    (lex.slice().len() <= 3).then_some(())
+}
+
+fn comment(lex: &mut Lexer<Token>) -> Skip {
+    lex.extras.comments += 1;
+    Skip
+}
+
+fn blank_line(lex: &mut Lexer<Token>) -> Filter<()> {
+    if lex.span().start == 0 {
+        Filter::Accept(())
+    } else {
+        Filter::Skip
+    }
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[logos(extras = Extras)]
+#[logos(error = LexingError)]
+#[logos(subpattern word = r"[a-z]+")]
+#[logos(skip r"[ \t]+")]
+enum Token {
+    #[regex("[0-9]+", even)]
+    Even,
+    #[regex("(?&word)", short)]
+    Word,
+    #[regex("#[^\n]*", comment)]
+    Comment,
+    #[token("\n", blank_line)]
+    Newline,
+    #[token(";", herring::logos::skip)]
+    Semicolon,
+}
+
+#[test]
+fn test_callbacks() {
+    let mut lex = Token::lexer("\n42 abc; # note\n7 abcd");
+    assert_eq!(lex.next(), Some(Ok(Token::Newline)));
+    assert_eq!(lex.next(), Some(Ok(Token::Even)));
+    assert_eq!(lex.next(), Some(Ok(Token::Word)));
+    assert_eq!(lex.next(), Some(Err(LexingError::Invalid)));
+    assert_eq!(lex.span(), 16..17);
+    assert_eq!(lex.next(), Some(Err(LexingError::Invalid)));
+    assert_eq!(lex.span(), 18..22);
+    assert_eq!(lex.next(), None);
+    assert_eq!(lex.extras.comments, 1);
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[logos(source = [u8])]
+#[logos(skip b"\x00+")]
+enum Binary {
+    #[token(b"\xCA\xFE")]
+    Cafe,
+}
+
+#[test]
+fn test_source() {
+    assert_lex(
+        &b"\xCA\xFE\x00\x00\xCA\xFE"[..],
+        &[
+            (Ok(Binary::Cafe), &[0xCA, 0xFE], 0..2),
+            (Ok(Binary::Cafe), &[0xCA, 0xFE], 4..6),
+        ],
+    );
+}
@@ -0,0 +1,68 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Number {
+    #[regex("[0-9]+")]
+    #[regex("[0-9]+", lookahead = r"\.\.")]
+    Int,
+    #[regex(r"[0-9]+\.[0-9]*")]
+    Float,
+    #[token("..")]
+    Range,
+}
+
+#[test]
+fn test_range() {
+    assert_lex(
+        "1..2 1.5 3.",
+        &[
+            (Ok(Number::Int), "1", 0..1),
+            (Ok(Number::Range), "..", 1..3),
+            (Ok(Number::Int), "2", 3..4),
+            (Ok(Number::Float), "1.5", 5..8),
+            (Ok(Number::Float), "3.", 9..11),
+        ],
+    );
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Call {
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("[a-z]+", lookahead = r"\(")]
+    Function,
+    #[regex("[a-z]+(?=:)")]
+    Label,
+    #[token("print", lookahead = r" *\(")]
+    Print,
+    #[token("(")]
+    LParen,
+    #[token(":")]
+    Colon,
+}
+
+#[test]
+fn test_call() {
+    assert_lex(
+        "foo bar(baz: qux (",
+        &[
+            (Ok(Call::Ident), "foo", 0..3),
+            (Ok(Call::Function), "bar", 4..7),
+            (Ok(Call::LParen), "(", 7..8),
+            (Ok(Call::Label), "baz", 8..11),
+            (Ok(Call::Colon), ":", 11..12),
+            (Ok(Call::Ident), "qux", 13..16),
+            (Ok(Call::LParen), "(", 17..18),
+        ],
+    );
+    assert_lex(
+        "print  (print",
+        &[
+            (Ok(Call::Print), "print", 0..5),
+            (Ok(Call::LParen), "(", 7..8),
+            (Ok(Call::Ident), "print", 8..13),
+        ],
+    );
+}
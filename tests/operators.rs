@@ -0,0 +1,108 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip r"[ \n]+")]
+enum Keywords {
+    #[regex("[a-z]+", except = ["if", "else"])]
+    Ident,
+    #[token("if")]
+    If,
+    #[token("else")]
+    Else,
+    #[regex("[a-z0-9]+", and = "[a-z]+[0-9]+|[0-9]+[a-z]+")]
+    Mixed,
+    #[regex(r"(?s)/\*.*\*/", except = r"(?s)/\*.*\*/.+")]
+    Comment,
+    #[token("*")]
+    Star,
+    #[token("/")]
+    Slash,
+}
+
+#[test]
+fn test_except() {
+    assert_lex(
+        "if iff else elsewhere",
+        &[
+            (Ok(Keywords::If), "if", 0..2),
+            (Ok(Keywords::Ident), "iff", 3..6),
+            (Ok(Keywords::Else), "else", 7..11),
+            (Ok(Keywords::Ident), "elsewhere", 12..21),
+        ],
+    );
+}
+
+#[test]
+fn test_and() {
+    assert_lex(
+        "abc1 1abc a1b",
+        &[
+            (Ok(Keywords::Mixed), "abc1", 0..4),
+            (Ok(Keywords::Mixed), "1abc", 5..9),
+            (Ok(Keywords::Mixed), "a1", 10..12),
+            (Ok(Keywords::Ident), "b", 12..13),
+        ],
+    );
+}
+
+#[test]
+fn test_comment() {
+    assert_lex(
+        "/* a\n*/ b */",
+        &[
+            (Ok(Keywords::Comment), "/* a\n*/", 0..7),
+            (Ok(Keywords::Ident), "b", 8..9),
+            (Ok(Keywords::Star), "*", 10..11),
+            (Ok(Keywords::Slash), "/", 11..12),
+        ],
+    );
+}
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip r"[ \n]+")]
+#[herring(subpattern body = not(r"(?s).*\*/.*"))]
+#[herring(subpattern word = not("if|else"))]
+#[herring(subpattern nb = not("(?s:.)*b(?s:.)*"))]
+enum Complement {
+    #[regex(r"/\*(?&body)\*/")]
+    Comment,
+    #[regex("[a-z]+", and = "(?&word)")]
+    Ident,
+    #[token("if")]
+    If,
+    #[token("*")]
+    Star,
+    #[token("/")]
+    Slash,
+    #[regex("(?:Z(?&nb))+")]
+    Zs,
+}
+
+#[test]
+fn test_complement() {
+    assert_lex(
+        "/* a\n*/ b */ if iff else",
+        &[
+            (Ok(Complement::Comment), "/* a\n*/", 0..7),
+            (Ok(Complement::Ident), "b", 8..9),
+            (Ok(Complement::Star), "*", 10..11),
+            (Ok(Complement::Slash), "/", 11..12),
+            (Ok(Complement::If), "if", 13..15),
+            (Ok(Complement::Ident), "iff", 16..19),
+            (Ok(Complement::Ident), "els", 20..23),
+            (Ok(Complement::Ident), "e", 23..24),
+        ],
+    );
+}
+
+#[test]
+fn test_repeated_complement() {
+    assert_lex(
+        "ZaZZ/ ZbZ",
+        &[
+            (Ok(Complement::Zs), "ZaZZ/ Z", 0..7),
+            (Ok(Complement::Ident), "b", 7..8),
+            (Ok(Complement::Zs), "Z", 8..9),
+        ],
+    );
+}
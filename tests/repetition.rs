@@ -0,0 +1,32 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip " +")]
+enum Token {
+    #[regex("#[0-9a-fA-F]{2,4}")]
+    Color,
+    #[regex(r"\p{Greek}{1,3}")]
+    Greek,
+    #[regex("(ab|cd){2,}")]
+    Pairs,
+    #[regex("x{3}")]
+    Xs,
+}
+
+#[test]
+fn test_bounded() {
+    assert_lex(
+        "#fF #0a1b2 αβγδ ab abcdab xxxx",
+        &[
+            (Ok(Token::Color), "#fF", 0..3),
+            (Ok(Token::Color), "#0a1b", 4..9),
+            (Err(()), "2", 9..10),
+            (Ok(Token::Greek), "αβγ", 11..17),
+            (Ok(Token::Greek), "δ", 17..19),
+            (Err(()), "ab", 20..22),
+            (Ok(Token::Pairs), "abcdab", 23..29),
+            (Ok(Token::Xs), "xxx", 30..33),
+            (Err(()), "x", 33..34),
+        ],
+    );
+}
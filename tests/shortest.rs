@@ -0,0 +1,48 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(skip r"[ \n]+")]
+enum C {
+    #[regex(r"(?s)/\*.*?\*/")]
+    Comment,
+    #[regex("[a-z]+")]
+    Ident,
+    #[token("*")]
+    Star,
+    #[token("/")]
+    Slash,
+}
+
+#[test]
+fn test_lazy_comment() {
+    assert_lex(
+        "a /* b\n */ c */ d",
+        &[
+            (Ok(C::Ident), "a", 0..1),
+            (Ok(C::Comment), "/* b\n */", 2..10),
+            (Ok(C::Ident), "c", 11..12),
+            (Ok(C::Star), "*", 13..14),
+            (Ok(C::Slash), "/", 14..15),
+            (Ok(C::Ident), "d", 16..17),
+        ],
+    );
+}
+
+#[derive(Herring, Debug, PartialEq)]
+enum Heredoc {
+    #[regex("<<EOF\n(.*\n)*EOF", shortest)]
+    Heredoc,
+    #[regex("[A-Z\n]+")]
+    Text,
+}
+
+#[test]
+fn test_shortest() {
+    assert_lex(
+        "<<EOF\nA\nEOF\nB\nEOF",
+        &[
+            (Ok(Heredoc::Heredoc), "<<EOF\nA\nEOF", 0..11),
+            (Ok(Heredoc::Text), "\nB\nEOF", 11..17),
+        ],
+    );
+}
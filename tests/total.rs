@@ -0,0 +1,30 @@
+use herring::{assert_lex, Herring};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(total)]
+#[herring(skip r"\s+")]
+enum Highlight {
+    #[regex("[a-zA-Z_][a-zA-Z0-9_]*")]
+    Ident,
+    #[regex(r#""([^"\\]|\\(?s:.))*""#)]
+    Str,
+    #[regex(r#""([^"\\]|\\(?s:.))*\\?"#, priority = 1)]
+    UnclosedStr,
+    #[regex(r"(?s:.)", priority = 0)]
+    Other,
+}
+
+#[test]
+fn test_total() {
+    assert_lex(
+        r#"let s = "a\"b" + "é"#,
+        &[
+            (Ok(Highlight::Ident), "let", 0..3),
+            (Ok(Highlight::Ident), "s", 4..5),
+            (Ok(Highlight::Other), "=", 6..7),
+            (Ok(Highlight::Str), r#""a\"b""#, 8..14),
+            (Ok(Highlight::Other), "+", 15..16),
+            (Ok(Highlight::UnclosedStr), "\"é", 17..20),
+        ],
+    );
+}
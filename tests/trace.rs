@@ -0,0 +1,92 @@
+use herring::{Herring, Lexer, PrettyTrace, Span, Trace};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+#[derive(Herring, Debug, PartialEq)]
+#[herring(trace)]
+#[herring(skip " +")]
+enum Token {
+    #[token("if")]
+    If,
+    #[regex("[a-z]+")]
+    Ident,
+    #[token(".")]
+    Dot,
+    #[token("...")]
+    Ellipsis,
+}
+
+#[derive(Clone, Default)]
+struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn pretty_trace() {
+    let writer = SharedWriter::default();
+    let mut lexer = Token::lexer("if ..");
+    lexer.set_tracer(PrettyTrace::with_writer(writer.clone()));
+    let tokens = lexer.collect::<Vec<_>>();
+    assert_eq!(tokens, [Ok(Token::If), Ok(Token::Dot), Ok(Token::Dot)]);
+    // the numbers of the states depend on the construction of the automaton, so only the
+    // decisions are compared
+    let output = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+    let decisions = output
+        .lines()
+        .filter(|line| !line.starts_with('S') && !line.starts_with("  '"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        decisions,
+        [
+            "  accept `Ident` until 1",
+            "  accept `If` until 2",
+            "emit `If` 0..2",
+            "  accept `skipped regex` until 3",
+            "  accept `Dot` until 4",
+            "  backtrack from 5 to 4",
+            "emit `Dot` 3..4",
+            "  accept `Dot` until 5",
+            "emit `Dot` 4..5",
+        ]
+    );
+    assert!(output.starts_with("S0 @0\n  'i' @0\n"), "{output}");
+}
+
+#[derive(Clone, Default)]
+struct Emitted(Arc<Mutex<Vec<String>>>);
+
+impl Trace for Emitted {
+    fn token_emitted(&mut self, token: Option<&str>, span: Span) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("{} {span:?}", token.unwrap_or("error")));
+    }
+}
+
+#[test]
+fn emitted_tokens() {
+    let emitted = Emitted::default();
+    let mut lexer = Token::lexer("x?...");
+    lexer.set_tracer(emitted.clone());
+    let tokens = lexer.collect::<Vec<_>>();
+    assert_eq!(tokens, [Ok(Token::Ident), Err(()), Ok(Token::Ellipsis)]);
+    assert_eq!(
+        *emitted.0.lock().unwrap(),
+        ["Ident 0..1", "error 1..2", "Ellipsis 2..5"]
+    );
+}
+
+#[test]
+fn traced_lexer_is_send() {
+    let mut lexer = Token::lexer("if");
+    lexer.set_tracer(PrettyTrace::new());
+    let _: Lexer<Token> = std::thread::spawn(move || lexer).join().unwrap();
+}